@@ -0,0 +1,266 @@
+use std::fmt;
+
+const CLIENT_ID: &str = "CLIENT_ID";
+const PANE_ID: &str = "ZELLIJ_PANE_ID";
+const RUNNING_COMMAND: &str = "RUNNING_COMMAND";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaneKind {
+    Terminal,
+    Plugin,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientInfo {
+    pub client_id: u16,
+    pub pane_id: u32,
+    pub pane_kind: PaneKind,
+    pub running_command: Option<String>,
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    MissingHeader,
+    MissingColumn(&'static str),
+    InvalidRow { row: String, reason: String },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::MissingHeader => write!(f, "list-clients output has no header row"),
+            ParseError::MissingColumn(column) => {
+                write!(f, "list-clients header has no {} column", column)
+            }
+            ParseError::InvalidRow { row, reason } => {
+                write!(f, "invalid list-clients row {:?}: {}", row, reason)
+            }
+        }
+    }
+}
+
+struct Header {
+    len: usize,
+    client_id: usize,
+    pane_id: usize,
+    running_command: usize,
+}
+
+impl Header {
+    fn parse(line: &str) -> Result<Self, ParseError> {
+        let columns = line.split_whitespace().collect::<Vec<&str>>();
+        let position = |name: &'static str| {
+            columns
+                .iter()
+                .position(|column| *column == name)
+                .ok_or(ParseError::MissingColumn(name))
+        };
+
+        Ok(Self {
+            len: columns.len(),
+            client_id: position(CLIENT_ID)?,
+            pane_id: position(PANE_ID)?,
+            running_command: position(RUNNING_COMMAND)?,
+        })
+    }
+
+    // The running command may contain spaces, every other column is a single token
+    fn split_row<'a>(&self, row: &'a str) -> Result<Vec<Vec<&'a str>>, ParseError> {
+        let tokens = row.split_whitespace().collect::<Vec<&str>>();
+        if tokens.len() < self.len {
+            return Err(invalid_row(
+                row,
+                format!("expected {} columns, found {}", self.len, tokens.len()),
+            ));
+        }
+
+        let command_end = tokens.len() - (self.len - 1 - self.running_command);
+        let mut columns = Vec::with_capacity(self.len);
+        columns.extend(tokens[..self.running_command].iter().map(|t| vec![*t]));
+        columns.push(tokens[self.running_command..command_end].to_vec());
+        columns.extend(tokens[command_end..].iter().map(|t| vec![*t]));
+        Ok(columns)
+    }
+}
+
+pub fn parse_client_list(output: &str) -> Result<Vec<ClientInfo>, ParseError> {
+    let mut lines = output.lines().filter(|line| !line.trim().is_empty());
+    let header = Header::parse(lines.next().ok_or(ParseError::MissingHeader)?)?;
+
+    lines.map(|row| parse_row(&header, row)).collect()
+}
+
+fn parse_row(header: &Header, row: &str) -> Result<ClientInfo, ParseError> {
+    let columns = header.split_row(row)?;
+
+    let client_id = columns[header.client_id][0]
+        .parse()
+        .map_err(|_| invalid_row(row, format!("{} is not a number", CLIENT_ID)))?;
+    let (pane_kind, pane_id) = parse_pane_id(columns[header.pane_id][0])
+        .ok_or_else(|| invalid_row(row, format!("unrecognized {}", PANE_ID)))?;
+
+    let command = &columns[header.running_command];
+    let (running_command, args) = match command.split_first() {
+        Some((program, args)) if *program != "N/A" => (
+            Some(program.to_string()),
+            args.iter().map(|arg| arg.to_string()).collect(),
+        ),
+        _ => (None, Vec::new()),
+    };
+
+    Ok(ClientInfo {
+        client_id,
+        pane_id,
+        pane_kind,
+        running_command,
+        args,
+    })
+}
+
+fn parse_pane_id(s: &str) -> Option<(PaneKind, u32)> {
+    if let Some(id) = s.strip_prefix("terminal_") {
+        return Some((PaneKind::Terminal, id.parse().ok()?));
+    }
+    if let Some(id) = s.strip_prefix("plugin_") {
+        return Some((PaneKind::Plugin, id.parse().ok()?));
+    }
+    None
+}
+
+fn invalid_row(row: &str, reason: String) -> ParseError {
+    ParseError::InvalidRow {
+        row: row.to_string(),
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(
+        client_id: u16,
+        pane_kind: PaneKind,
+        pane_id: u32,
+        command: Option<&str>,
+        args: &[&str],
+    ) -> ClientInfo {
+        ClientInfo {
+            client_id,
+            pane_id,
+            pane_kind,
+            running_command: command.map(str::to_string),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    // `zellij action list-clients` of Zellij 0.40.1 with a shell, Neovim and the strider plugin
+    // focused by three clients. Columns are padded to 9, 14 and 15 characters
+    const ZELLIJ_0_40_1: &str = concat!(
+        "CLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND\n",
+        "1         terminal_0     N/A            \n",
+        "2         terminal_2     nvim --clean src/main.rs\n",
+        "3         plugin_1       zellij:strider \n",
+    );
+
+    // Zellij 0.41.2 renders the same columns, a command without arguments keeps the space that
+    // separates it from the empty argument list
+    const ZELLIJ_0_41_2: &str = concat!(
+        "CLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND\n",
+        "1         terminal_3     lazygit        \n",
+        "2         terminal_3     lazygit        \n",
+    );
+
+    #[test]
+    fn zellij_0_40_1_shell_command_and_plugin_panes() {
+        assert_eq!(
+            parse_client_list(ZELLIJ_0_40_1),
+            Ok(vec![
+                client(1, PaneKind::Terminal, 0, None, &[]),
+                client(
+                    2,
+                    PaneKind::Terminal,
+                    2,
+                    Some("nvim"),
+                    &["--clean", "src/main.rs"]
+                ),
+                client(3, PaneKind::Plugin, 1, Some("zellij:strider"), &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn zellij_0_41_2_command_without_arguments() {
+        assert_eq!(
+            parse_client_list(ZELLIJ_0_41_2),
+            Ok(vec![
+                client(1, PaneKind::Terminal, 3, Some("lazygit"), &[]),
+                client(2, PaneKind::Terminal, 3, Some("lazygit"), &[]),
+            ])
+        );
+    }
+
+    // The remaining outputs are malformed on purpose, no Zellij version prints them
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        let output = "\n\nCLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND\n\n1 terminal_1 nvim\n\n";
+        assert_eq!(
+            parse_client_list(output),
+            Ok(vec![client(1, PaneKind::Terminal, 1, Some("nvim"), &[])])
+        );
+    }
+
+    #[test]
+    fn missing_header() {
+        assert_eq!(parse_client_list(""), Err(ParseError::MissingHeader));
+        assert_eq!(parse_client_list("  \n"), Err(ParseError::MissingHeader));
+    }
+
+    #[test]
+    fn missing_column() {
+        assert_eq!(
+            parse_client_list("CLIENT_ID RUNNING_COMMAND\n1 nvim\n"),
+            Err(ParseError::MissingColumn(PANE_ID))
+        );
+    }
+
+    #[test]
+    fn short_row() {
+        let output = "CLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND\n1 terminal_1\n";
+        assert_eq!(
+            parse_client_list(output),
+            Err(ParseError::InvalidRow {
+                row: "1 terminal_1".to_string(),
+                reason: "expected 3 columns, found 2".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn non_numeric_client_id() {
+        let output = "CLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND\nfirst terminal_1 nvim\n";
+        assert_eq!(
+            parse_client_list(output),
+            Err(ParseError::InvalidRow {
+                row: "first terminal_1 nvim".to_string(),
+                reason: "CLIENT_ID is not a number".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn unrecognized_pane_id() {
+        for pane_id in ["floating_1", "7"] {
+            let output = format!(
+                "CLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND\n1 {} nvim\n",
+                pane_id
+            );
+            assert!(matches!(
+                parse_client_list(&output),
+                Err(ParseError::InvalidRow { .. })
+            ));
+        }
+    }
+}
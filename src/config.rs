@@ -0,0 +1,534 @@
+use zellij_tile::prelude::Direction;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::keys::{expand_template, parse_vim_keys, parse_zellij_keys, KeyChord, KeyEncoding};
+use crate::programs::{
+    parse_program_patterns, ProgramPattern, DEFAULT_NVIM_PROGRAMS, DEFAULT_PASSTHROUGH_PROGRAMS,
+    DEFAULT_WRAPPER_PROGRAMS,
+};
+
+const MOD_VALUES: &[&str] = &["ctrl", "alt"];
+const BOOL_VALUES: &[&str] = &["true", "false"];
+const EDGE_ACTION_VALUES: &[&str] = &["nothing", "wrap", "tab", "new_pane", "new_tab", "session"];
+const TIMEOUT_ACTION_VALUES: &[&str] = &["execute", "drop"];
+const KEY_ENCODING_VALUES: &[&str] = &["legacy", "modify_other_keys", "kitty"];
+const COMMAND_KINDS: &[&str] = &["move", "resize", "shrink", "swap", "previous"];
+const DIRECTION_VALUES: &[&str] = &["left", "right", "up", "down"];
+
+// Options shared by all queued commands, they cannot differ between messages
+const GLOBAL_KEYS: &[&str] = &["command_timeout", "max_queued_commands"];
+
+// Options of the `MessagePlugin` keybind action that Zellij also passes to the plugin
+const MESSAGE_PLUGIN_KEYS: &[&str] = &[
+    "name",
+    "payload",
+    "launch_new",
+    "skip_cache",
+    "floating",
+    "title",
+    "cwd",
+];
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Left,
+    Direction::Down,
+    Direction::Up,
+    Direction::Right,
+];
+
+#[derive(Clone, PartialEq)]
+pub struct Config {
+    pub move_mod: Mod,
+    pub resize_mod: Mod,
+    pub key_encoding: KeyEncoding,
+    pub resize_step: Option<ResizeStep>,
+    pub on_edge: EdgeAction,
+    pub split_command: Option<String>,
+    pub split_cwd: Option<String>,
+    pub nvim_socket: Option<String>,
+    pub nvim_programs: Vec<ProgramPattern>,
+    pub command_timeout: f64,
+    pub timeout_action: TimeoutAction,
+    pub max_queued_commands: usize,
+    pub cache_ttl: f64,
+    pub passthrough_programs: Vec<ProgramPattern>,
+    pub wrapper_programs: Vec<ProgramPattern>,
+    pub profiles: BTreeMap<String, BTreeMap<CommandKind, String>>,
+    pub key_bindings: BTreeMap<(CommandKind, Option<Direction>), Vec<KeyChord>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResizeStep {
+    Cells(usize),
+    Percent(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdgeAction {
+    Nothing,
+    Wrap,
+    Tab,
+    NewPane,
+    NewTab,
+    Session,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeoutAction {
+    Execute,
+    Drop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CommandKind {
+    Move,
+    Resize,
+    Shrink,
+    Swap,
+    Previous,
+}
+
+impl CommandKind {
+    fn has_direction(&self) -> bool {
+        *self != CommandKind::Previous
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mod {
+    Ctrl,
+    Alt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    pub key: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            move_mod: Mod::Ctrl,
+            resize_mod: Mod::Alt,
+            key_encoding: KeyEncoding::Legacy,
+            resize_step: None,
+            on_edge: EdgeAction::Nothing,
+            split_command: None,
+            split_cwd: None,
+            nvim_socket: None,
+            nvim_programs: parse_program_patterns(DEFAULT_NVIM_PROGRAMS).unwrap(),
+            command_timeout: 1.0,
+            timeout_action: TimeoutAction::Execute,
+            max_queued_commands: 8,
+            cache_ttl: 1.0,
+            passthrough_programs: parse_program_patterns(DEFAULT_PASSTHROUGH_PROGRAMS).unwrap(),
+            wrapper_programs: parse_program_patterns(DEFAULT_WRAPPER_PROGRAMS).unwrap(),
+            profiles: BTreeMap::new(),
+            key_bindings: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    // Invalid values keep their default, every problem is collected instead of aborting
+    pub fn parse(configuration: &BTreeMap<String, String>) -> (Self, Vec<ConfigIssue>) {
+        let mut config = Config::default();
+        let issues = configuration
+            .iter()
+            .filter_map(|(key, value)| config.apply(key, value).err())
+            .collect();
+        (config, issues)
+    }
+
+    pub fn with_overrides(&self, overrides: &BTreeMap<String, String>) -> (Self, Vec<ConfigIssue>) {
+        let mut config = self.clone();
+        let issues = overrides
+            .iter()
+            .filter_map(|(key, value)| {
+                if GLOBAL_KEYS.contains(&key.as_str()) {
+                    return Some(warning(
+                        key,
+                        "cannot be overridden per message, it is ignored".to_string(),
+                    ));
+                }
+                config.apply(key, value).err()
+            })
+            .collect();
+        (config, issues)
+    }
+
+    fn apply(&mut self, key: &str, value: &str) -> Result<(), ConfigIssue> {
+        match key {
+            "move_mod" => self.move_mod = parse_mod(key, value)?,
+            "resize_mod" => self.resize_mod = parse_mod(key, value)?,
+            "key_encoding" => self.key_encoding = parse_key_encoding(key, value)?,
+            "resize_step" => self.resize_step = Some(parse_resize_step(key, value)?),
+            "on_edge" => self.on_edge = parse_edge_action(key, value)?,
+            "split_command" => {
+                self.split_command = Some(value.trim().to_string()).filter(|c| !c.is_empty())
+            }
+            "split_cwd" => self.split_cwd = Some(value.to_string()).filter(|cwd| cwd != "inherit"),
+            "nvim_socket" => {
+                self.nvim_socket = Some(value.trim().to_string()).filter(|s| !s.is_empty())
+            }
+            "command_timeout" => self.command_timeout = parse_seconds(key, value)?,
+            "timeout_action" => self.timeout_action = parse_timeout_action(key, value)?,
+            "max_queued_commands" => self.max_queued_commands = parse_count(key, value)?,
+            "cache_ttl" => self.cache_ttl = parse_ttl(key, value)?,
+            // Shorthand for `on_edge "wrap"`
+            "wrap" => {
+                if parse_bool(key, value)? {
+                    self.on_edge = EdgeAction::Wrap;
+                }
+            }
+            "passthrough_programs" => self.passthrough_programs = parse_patterns(key, value)?,
+            "wrapper_programs" => self.wrapper_programs = parse_patterns(key, value)?,
+            "nvim_programs" => self.nvim_programs = parse_patterns(key, value)?,
+            _ if key.starts_with("profile.") => self.apply_profile(key, value)?,
+            _ if key.starts_with("keys.") => self.apply_key_binding(key, value)?,
+            _ if MESSAGE_PLUGIN_KEYS.contains(&key) => {}
+            _ => return Err(warning(key, "unknown option, it is ignored".to_string())),
+        }
+        Ok(())
+    }
+
+    fn apply_profile(&mut self, key: &str, value: &str) -> Result<(), ConfigIssue> {
+        let (program, command) = key["profile.".len()..]
+            .rsplit_once('.')
+            .ok_or_else(|| error(key, "expected profile.<program>.<command>".to_string()))?;
+        let command = parse_command_kind(key, command)?;
+        if !command.has_direction() && (value.contains("{dir}") || value.contains("{arrow}")) {
+            return Err(error(
+                key,
+                "{dir} and {arrow} are only replaced for directional commands".to_string(),
+            ));
+        }
+        for direction in DIRECTIONS.iter() {
+            let direction = Some(direction).filter(|_| command.has_direction());
+            parse_vim_keys(&expand_template(value, direction)).map_err(|err| error(key, err))?;
+        }
+
+        self.profiles
+            .entry(program.to_string())
+            .or_default()
+            .insert(command, value.to_string());
+        Ok(())
+    }
+
+    fn apply_key_binding(&mut self, key: &str, value: &str) -> Result<(), ConfigIssue> {
+        let (command, direction) = match key["keys.".len()..].split_once('.') {
+            Some((command, direction)) => (command, Some(direction)),
+            None => (&key["keys.".len()..], None),
+        };
+        let command = parse_command_kind(key, command)?;
+        let direction = match (command.has_direction(), direction) {
+            (true, Some(direction)) => Some(
+                string_to_direction(direction)
+                    .ok_or_else(|| illegal_value(key, direction, DIRECTION_VALUES))?,
+            ),
+            (false, None) => None,
+            (true, None) => {
+                return Err(error(
+                    key,
+                    "expected keys.<command>.<direction>".to_string(),
+                ))
+            }
+            (false, Some(_)) => return Err(error(key, "expected keys.previous".to_string())),
+        };
+        let keys = parse_zellij_keys(value).map_err(|err| error(key, err))?;
+
+        self.key_bindings.insert((command, direction), keys);
+        Ok(())
+    }
+}
+
+pub fn string_to_direction(s: &str) -> Option<Direction> {
+    match s {
+        "left" => Some(Direction::Left),
+        "right" => Some(Direction::Right),
+        "up" => Some(Direction::Up),
+        "down" => Some(Direction::Down),
+        _ => None,
+    }
+}
+
+fn parse_command_kind(key: &str, value: &str) -> Result<CommandKind, ConfigIssue> {
+    match value {
+        "move" => Ok(CommandKind::Move),
+        "resize" => Ok(CommandKind::Resize),
+        "shrink" => Ok(CommandKind::Shrink),
+        "swap" => Ok(CommandKind::Swap),
+        "previous" => Ok(CommandKind::Previous),
+        _ => Err(illegal_value(key, value, COMMAND_KINDS)),
+    }
+}
+
+fn parse_mod(key: &str, value: &str) -> Result<Mod, ConfigIssue> {
+    match value.to_lowercase().as_str() {
+        "ctrl" => Ok(Mod::Ctrl),
+        "alt" => Ok(Mod::Alt),
+        _ => Err(illegal_value(key, value, MOD_VALUES)),
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, ConfigIssue> {
+    match value.to_lowercase().as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(illegal_value(key, value, BOOL_VALUES)),
+    }
+}
+
+fn parse_edge_action(key: &str, value: &str) -> Result<EdgeAction, ConfigIssue> {
+    match value.to_lowercase().as_str() {
+        "nothing" => Ok(EdgeAction::Nothing),
+        "wrap" => Ok(EdgeAction::Wrap),
+        "tab" => Ok(EdgeAction::Tab),
+        "new_pane" => Ok(EdgeAction::NewPane),
+        "new_tab" => Ok(EdgeAction::NewTab),
+        "session" => Ok(EdgeAction::Session),
+        _ => Err(illegal_value(key, value, EDGE_ACTION_VALUES)),
+    }
+}
+
+fn parse_timeout_action(key: &str, value: &str) -> Result<TimeoutAction, ConfigIssue> {
+    match value.to_lowercase().as_str() {
+        "execute" => Ok(TimeoutAction::Execute),
+        "drop" => Ok(TimeoutAction::Drop),
+        _ => Err(illegal_value(key, value, TIMEOUT_ACTION_VALUES)),
+    }
+}
+
+fn parse_seconds(key: &str, value: &str) -> Result<f64, ConfigIssue> {
+    match value.trim().parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(seconds),
+        _ => Err(error(
+            key,
+            format!(
+                "illegal value {:?}, expected a number of seconds (e.g. 0.5)",
+                value
+            ),
+        )),
+    }
+}
+
+// Unlike timeouts a TTL of 0 is allowed, it turns the cache off
+fn parse_ttl(key: &str, value: &str) -> Result<f64, ConfigIssue> {
+    match value.trim().parse::<f64>() {
+        Ok(seconds) if seconds >= 0.0 && seconds.is_finite() => Ok(seconds),
+        _ => Err(error(
+            key,
+            format!(
+                "illegal value {:?}, expected a number of seconds (e.g. 0.5)",
+                value
+            ),
+        )),
+    }
+}
+
+fn parse_count(key: &str, value: &str) -> Result<usize, ConfigIssue> {
+    match value.trim().parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(error(
+            key,
+            format!("illegal value {:?}, expected a positive number", value),
+        )),
+    }
+}
+
+fn parse_key_encoding(key: &str, value: &str) -> Result<KeyEncoding, ConfigIssue> {
+    match value.to_lowercase().as_str() {
+        "legacy" => Ok(KeyEncoding::Legacy),
+        "modify_other_keys" => Ok(KeyEncoding::ModifyOtherKeys),
+        "kitty" => Ok(KeyEncoding::Kitty),
+        _ => Err(illegal_value(key, value, KEY_ENCODING_VALUES)),
+    }
+}
+
+fn parse_resize_step(key: &str, value: &str) -> Result<ResizeStep, ConfigIssue> {
+    let (amount, step): (&str, fn(usize) -> ResizeStep) = match value.strip_suffix('%') {
+        Some(percent) => (percent, ResizeStep::Percent),
+        None => (value, ResizeStep::Cells),
+    };
+    match amount.trim().parse() {
+        Ok(amount) if amount > 0 => Ok(step(amount)),
+        _ => Err(error(
+            key,
+            format!(
+                "illegal value {:?}, expected a number of cells (e.g. 5) or a percentage (e.g. 10%)",
+                value
+            ),
+        )),
+    }
+}
+
+fn parse_patterns(key: &str, value: &str) -> Result<Vec<ProgramPattern>, ConfigIssue> {
+    parse_program_patterns(value).map_err(|err| error(key, format!("invalid regex: {}", err)))
+}
+
+fn illegal_value(key: &str, value: &str, allowed: &[&str]) -> ConfigIssue {
+    error(
+        key,
+        format!(
+            "illegal value {:?}, allowed values: {}",
+            value,
+            allowed.join(", ")
+        ),
+    )
+}
+
+fn error(key: &str, message: String) -> ConfigIssue {
+    ConfigIssue {
+        severity: Severity::Error,
+        key: key.to_string(),
+        message,
+    }
+}
+
+fn warning(key: &str, message: String) -> ConfigIssue {
+    ConfigIssue {
+        severity: Severity::Warning,
+        key: key.to_string(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(options: &[(&str, &str)]) -> BTreeMap<String, String> {
+        options
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn issue_keys(issues: &[ConfigIssue], severity: Severity) -> Vec<&str> {
+        issues
+            .iter()
+            .filter(|issue| issue.severity == severity)
+            .map(|issue| issue.key.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn every_invalid_value_is_reported_and_keeps_its_default() {
+        let (config, issues) = Config::parse(&options(&[
+            ("move_mod", "shift"),
+            ("on_edge", "jump"),
+            ("command_timeout", "-1"),
+        ]));
+        assert_eq!(
+            issue_keys(&issues, Severity::Error),
+            vec!["command_timeout", "move_mod", "on_edge"]
+        );
+        assert_eq!(issues.len(), 3);
+        assert!(config == Config::default());
+    }
+
+    #[test]
+    fn unknown_option_is_a_warning() {
+        let (config, issues) = Config::parse(&options(&[("move_modd", "alt")]));
+        assert_eq!(issue_keys(&issues, Severity::Warning), vec!["move_modd"]);
+        assert_eq!(issues.len(), 1);
+        assert!(config == Config::default());
+    }
+
+    #[test]
+    fn message_plugin_options_are_ignored() {
+        let (config, issues) = Config::parse(&options(&[
+            ("name", "move_focus"),
+            ("payload", "left"),
+            ("floating", "true"),
+            ("skip_cache", "false"),
+        ]));
+        assert!(issues.is_empty());
+        assert!(config == Config::default());
+    }
+
+    #[test]
+    fn queue_options_cannot_be_overridden_per_message() {
+        let (config, issues) = Config::default().with_overrides(&options(&[
+            ("command_timeout", "5"),
+            ("max_queued_commands", "1"),
+            ("on_edge", "wrap"),
+        ]));
+        assert_eq!(
+            issue_keys(&issues, Severity::Warning),
+            vec!["command_timeout", "max_queued_commands"]
+        );
+        assert_eq!(issues.len(), 2);
+        assert_eq!(config.command_timeout, 1.0);
+        assert_eq!(config.max_queued_commands, 8);
+        assert_eq!(config.on_edge, EdgeAction::Wrap);
+    }
+
+    #[test]
+    fn invalid_key_bindings_are_errors() {
+        let (config, issues) = Config::parse(&options(&[
+            ("keys.move", "Ctrl h"),
+            ("keys.move.sideways", "Ctrl h"),
+            ("keys.previous.left", "Ctrl p"),
+            ("keys.jump.left", "Ctrl h"),
+            ("keys.move.left", "Hyper h"),
+            ("keys.move.right", "Ctrl l"),
+        ]));
+        assert_eq!(
+            issue_keys(&issues, Severity::Error),
+            vec![
+                "keys.jump.left",
+                "keys.move",
+                "keys.move.left",
+                "keys.move.sideways",
+                "keys.previous.left",
+            ]
+        );
+        assert_eq!(issues.len(), 5);
+        assert_eq!(
+            config.key_bindings.keys().collect::<Vec<_>>(),
+            vec![&(CommandKind::Move, Some(Direction::Right))]
+        );
+    }
+
+    #[test]
+    fn invalid_profiles_are_errors() {
+        let (config, issues) = Config::parse(&options(&[
+            ("profile.helix", "<C-w>h"),
+            ("profile.helix.jump", "<C-w>h"),
+            ("profile.helix.move", "<C-w><Bogus>"),
+            ("profile.helix.resize", "<C-w>{dir}"),
+            ("profile.helix.previous", "<C-w>{arrow}"),
+        ]));
+        assert_eq!(
+            issue_keys(&issues, Severity::Error),
+            vec![
+                "profile.helix",
+                "profile.helix.jump",
+                "profile.helix.move",
+                "profile.helix.previous",
+            ]
+        );
+        assert_eq!(issues.len(), 4);
+        assert_eq!(
+            config.profiles["helix"].keys().collect::<Vec<_>>(),
+            vec![&CommandKind::Resize]
+        );
+    }
+}
@@ -0,0 +1,373 @@
+use zellij_tile::prelude::Direction;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Char(char),
+    Left,
+    Right,
+    Up,
+    Down,
+    Enter,
+    Esc,
+    Tab,
+    Backspace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub super_: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyEncoding {
+    Legacy,
+    ModifyOtherKeys,
+    Kitty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyChord {
+    pub key: Key,
+    pub modifiers: Modifiers,
+}
+
+impl KeyChord {
+    pub fn new(key: Key) -> Self {
+        Self {
+            key,
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    pub fn with_modifiers(key: Key, modifiers: Modifiers) -> Self {
+        Self { key, modifiers }
+    }
+}
+
+impl Modifiers {
+    // Modifier parameter shared by xterm and kitty sequences
+    fn param(&self) -> u8 {
+        1 + self.shift as u8 + 2 * self.alt as u8 + 4 * self.ctrl as u8 + 8 * self.super_ as u8
+    }
+}
+
+pub fn direction_key(direction: &Direction) -> Key {
+    match direction {
+        Direction::Left => Key::Char('h'),
+        Direction::Down => Key::Char('j'),
+        Direction::Up => Key::Char('k'),
+        Direction::Right => Key::Char('l'),
+    }
+}
+
+// Expands `{dir}` to hjkl and `{arrow}` to the arrow key name, e.g. `<C-{dir}>` or `<{arrow}>`
+pub fn expand_template(template: &str, direction: Option<&Direction>) -> String {
+    let direction = match direction {
+        Some(direction) => direction,
+        None => return template.to_string(),
+    };
+    let (letter, arrow) = match direction {
+        Direction::Left => ("h", "Left"),
+        Direction::Down => ("j", "Down"),
+        Direction::Up => ("k", "Up"),
+        Direction::Right => ("l", "Right"),
+    };
+    template.replace("{dir}", letter).replace("{arrow}", arrow)
+}
+
+// Parses Vim key notation, e.g. `<C-w>h`, `<Space>wl` or `<A-Left>`
+pub fn parse_vim_keys(s: &str) -> Result<Vec<KeyChord>, String> {
+    let mut chords = Vec::new();
+    let mut rest = s;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            if let Some(end) = rest.find('>') {
+                chords.push(parse_vim_key(&rest[1..end])?);
+                rest = &rest[end + 1..];
+                continue;
+            }
+        }
+        chords.push(KeyChord::new(Key::Char(c)));
+        rest = &rest[c.len_utf8()..];
+    }
+    Ok(chords)
+}
+
+fn parse_vim_key(s: &str) -> Result<KeyChord, String> {
+    let mut modifiers = Modifiers::default();
+    let mut name = s;
+
+    while name.len() > 2 && name.as_bytes()[1] == b'-' {
+        match name.as_bytes()[0].to_ascii_lowercase() {
+            b'c' => modifiers.ctrl = true,
+            b'a' | b'm' => modifiers.alt = true,
+            b's' => modifiers.shift = true,
+            _ => return Err(format!("unknown modifier in <{}>", s)),
+        }
+        name = &name[2..];
+    }
+
+    let key = match name.to_lowercase().as_str() {
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "cr" | "enter" | "return" => Key::Enter,
+        "esc" => Key::Esc,
+        "tab" => Key::Tab,
+        "bs" => Key::Backspace,
+        "space" => Key::Char(' '),
+        "lt" => Key::Char('<'),
+        "bslash" => Key::Char('\\'),
+        "bar" => Key::Char('|'),
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Key::Char(c),
+                _ => return Err(format!("unknown key <{}>", s)),
+            }
+        }
+    };
+    Ok(KeyChord { key, modifiers })
+}
+
+// Parses Zellij key notation, e.g. `Ctrl Shift h`, chords of a sequence are separated by `;`
+pub fn parse_zellij_keys(s: &str) -> Result<Vec<KeyChord>, String> {
+    s.split(';').map(parse_zellij_key).collect()
+}
+
+fn parse_zellij_key(s: &str) -> Result<KeyChord, String> {
+    let mut words = s.split_whitespace().collect::<Vec<&str>>();
+    let name = words.pop().ok_or_else(|| "empty key".to_string())?;
+
+    let mut modifiers = Modifiers::default();
+    for word in words {
+        match word.to_lowercase().as_str() {
+            "ctrl" => modifiers.ctrl = true,
+            "alt" => modifiers.alt = true,
+            "shift" => modifiers.shift = true,
+            "super" => modifiers.super_ = true,
+            _ => return Err(format!("unknown modifier {:?} in {:?}", word, s.trim())),
+        }
+    }
+
+    let key = match name.to_lowercase().as_str() {
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "enter" => Key::Enter,
+        "esc" => Key::Esc,
+        "tab" => Key::Tab,
+        "backspace" => Key::Backspace,
+        "space" => Key::Char(' '),
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Key::Char(c),
+                _ => return Err(format!("unknown key {:?} in {:?}", name, s.trim())),
+            }
+        }
+    };
+    Ok(KeyChord::with_modifiers(key, modifiers))
+}
+
+pub fn encode_keys(chords: &[KeyChord], encoding: KeyEncoding) -> String {
+    chords
+        .iter()
+        .map(|chord| match encoding {
+            KeyEncoding::Legacy => encode_legacy(chord),
+            KeyEncoding::ModifyOtherKeys => encode_modify_other_keys(chord),
+            KeyEncoding::Kitty => encode_kitty(chord),
+        })
+        .collect()
+}
+
+fn encode_legacy(chord: &KeyChord) -> String {
+    let modifiers = &chord.modifiers;
+    let c = match chord.key {
+        Key::Char(c) if modifiers.ctrl => ctrl_char(c),
+        Key::Char(c) if modifiers.shift => c.to_ascii_uppercase(),
+        Key::Char(c) => c,
+        Key::Enter => '\r',
+        Key::Esc => '\u{1b}',
+        Key::Tab => '\t',
+        Key::Backspace => '\u{7f}',
+        Key::Left | Key::Right | Key::Up | Key::Down => return encode_arrow(chord),
+    };
+    if modifiers.alt {
+        return format!("\u{1b}{}", c);
+    }
+    c.to_string()
+}
+
+// `CSI 27 ; modifiers ; code ~` as sent by xterm with `modifyOtherKeys` level 2
+fn encode_modify_other_keys(chord: &KeyChord) -> String {
+    let param = chord.modifiers.param();
+    match key_code(chord) {
+        Some(code) if param > 1 => format!("\u{1b}[27;{};{}~", param, code),
+        Some(_) => encode_legacy(chord),
+        None => encode_arrow(chord),
+    }
+}
+
+// `CSI code ; modifiers u` from the kitty keyboard protocol, shift is a modifier of the base key
+fn encode_kitty(chord: &KeyChord) -> String {
+    let param = chord.modifiers.param();
+    let code = match chord.key {
+        Key::Char(c) => c.to_ascii_lowercase() as u32,
+        _ => match key_code(chord) {
+            Some(code) => code,
+            None => return encode_arrow(chord),
+        },
+    };
+    match (chord.key, param) {
+        (Key::Esc, 1) => "\u{1b}[27u".to_string(),
+        (_, 1) => encode_legacy(chord),
+        _ => format!("\u{1b}[{};{}u", code, param),
+    }
+}
+
+fn key_code(chord: &KeyChord) -> Option<u32> {
+    match chord.key {
+        Key::Char(c) if chord.modifiers.shift => Some(c.to_ascii_uppercase() as u32),
+        Key::Char(c) => Some(c as u32),
+        Key::Enter => Some(13),
+        Key::Esc => Some(27),
+        Key::Tab => Some(9),
+        Key::Backspace => Some(127),
+        Key::Left | Key::Right | Key::Up | Key::Down => None,
+    }
+}
+
+fn encode_arrow(chord: &KeyChord) -> String {
+    let arrow = match chord.key {
+        Key::Up => 'A',
+        Key::Down => 'B',
+        Key::Right => 'C',
+        _ => 'D',
+    };
+    match chord.modifiers.param() {
+        1 => format!("\u{1b}[{}", arrow),
+        param => format!("\u{1b}[1;{}{}", param, arrow),
+    }
+}
+
+fn ctrl_char(c: char) -> char {
+    match c.to_ascii_lowercase() {
+        c @ 'a'..='z' => ((c as u8) & 0x1f) as char,
+        c @ '['..='_' => ((c as u8) & 0x1f) as char,
+        '@' | ' ' => '\u{0}',
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CTRL: Modifiers = Modifiers {
+        ctrl: true,
+        alt: false,
+        shift: false,
+        super_: false,
+    };
+    const ALT: Modifiers = Modifiers {
+        ctrl: false,
+        alt: true,
+        shift: false,
+        super_: false,
+    };
+    const CTRL_SHIFT: Modifiers = Modifiers {
+        ctrl: true,
+        alt: false,
+        shift: true,
+        super_: false,
+    };
+    const ALT_SHIFT: Modifiers = Modifiers {
+        ctrl: false,
+        alt: true,
+        shift: true,
+        super_: false,
+    };
+    const SUPER: Modifiers = Modifiers {
+        ctrl: false,
+        alt: false,
+        shift: false,
+        super_: true,
+    };
+
+    // Bytes for the legacy, modifyOtherKeys and kitty encodings
+    fn assert_encodes(chord: KeyChord, legacy: &str, modify_other_keys: &str, kitty: &str) {
+        assert_eq!(encode_keys(&[chord], KeyEncoding::Legacy), legacy);
+        assert_eq!(
+            encode_keys(&[chord], KeyEncoding::ModifyOtherKeys),
+            modify_other_keys
+        );
+        assert_eq!(encode_keys(&[chord], KeyEncoding::Kitty), kitty);
+    }
+
+    #[test]
+    fn unmodified_char() {
+        assert_encodes(KeyChord::new(Key::Char('h')), "h", "h", "h");
+    }
+
+    #[test]
+    fn ctrl() {
+        let ctrl_h = KeyChord::with_modifiers(Key::Char('h'), CTRL);
+        assert_encodes(ctrl_h, "\x08", "\x1b[27;5;104~", "\x1b[104;5u");
+        let ctrl_j = KeyChord::with_modifiers(Key::Char('j'), CTRL);
+        assert_encodes(ctrl_j, "\x0a", "\x1b[27;5;106~", "\x1b[106;5u");
+        let ctrl_backslash = KeyChord::with_modifiers(Key::Char('\\'), CTRL);
+        assert_encodes(ctrl_backslash, "\x1c", "\x1b[27;5;92~", "\x1b[92;5u");
+    }
+
+    #[test]
+    fn alt() {
+        let alt_h = KeyChord::with_modifiers(Key::Char('h'), ALT);
+        assert_encodes(alt_h, "\x1bh", "\x1b[27;3;104~", "\x1b[104;3u");
+    }
+
+    #[test]
+    fn shift_combinations() {
+        let ctrl_shift_h = KeyChord::with_modifiers(Key::Char('h'), CTRL_SHIFT);
+        assert_encodes(ctrl_shift_h, "\x08", "\x1b[27;6;72~", "\x1b[104;6u");
+        let alt_shift_h = KeyChord::with_modifiers(Key::Char('h'), ALT_SHIFT);
+        assert_encodes(alt_shift_h, "\x1bH", "\x1b[27;4;72~", "\x1b[104;4u");
+    }
+
+    #[test]
+    fn super_modifier() {
+        let super_h = KeyChord::with_modifiers(Key::Char('h'), SUPER);
+        assert_encodes(super_h, "h", "\x1b[27;9;104~", "\x1b[104;9u");
+    }
+
+    #[test]
+    fn arrows() {
+        assert_encodes(KeyChord::new(Key::Up), "\x1b[A", "\x1b[A", "\x1b[A");
+        let ctrl_left = KeyChord::with_modifiers(Key::Left, CTRL);
+        assert_encodes(ctrl_left, "\x1b[1;5D", "\x1b[1;5D", "\x1b[1;5D");
+        let alt_shift_down = KeyChord::with_modifiers(Key::Down, ALT_SHIFT);
+        assert_encodes(alt_shift_down, "\x1b[1;4B", "\x1b[1;4B", "\x1b[1;4B");
+    }
+
+    #[test]
+    fn esc() {
+        assert_encodes(KeyChord::new(Key::Esc), "\x1b", "\x1b", "\x1b[27u");
+        let ctrl_esc = KeyChord::with_modifiers(Key::Esc, CTRL);
+        assert_encodes(ctrl_esc, "\x1b", "\x1b[27;5;27~", "\x1b[27;5u");
+    }
+
+    #[test]
+    fn sequences() {
+        let keys = parse_vim_keys("<C-w>h").unwrap();
+        assert_eq!(encode_keys(&keys, KeyEncoding::Legacy), "\x17h");
+        assert_eq!(encode_keys(&keys, KeyEncoding::Kitty), "\x1b[119;5uh");
+        let keys = parse_zellij_keys("Ctrl w; Shift h").unwrap();
+        assert_eq!(encode_keys(&keys, KeyEncoding::Legacy), "\x17H");
+    }
+}
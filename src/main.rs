@@ -1,237 +1,1080 @@
+mod client_list;
+mod config;
+mod keys;
+mod panes;
+mod programs;
+
+use ansi_term::Colour::{Red, Yellow};
+use chrono::{DateTime, Utc};
 use zellij_tile::prelude::*;
 
 use std::collections::{BTreeMap, VecDeque};
 
+use client_list::{parse_client_list, ClientInfo, PaneKind};
+use config::{
+    string_to_direction, CommandKind, Config, ConfigIssue, EdgeAction, Mod, ResizeStep, Severity,
+    TimeoutAction,
+};
+use keys::{direction_key, encode_keys, expand_template, parse_vim_keys, Key, KeyChord, Modifiers};
+use panes::{PaneId, PaneTracker};
+use programs::{unwrap_program, ProgramPattern};
+
+#[derive(Default)]
 struct State {
     permissions_granted: bool,
+    permissions_denied: bool,
+    // Messages received before the permissions are granted, replayed once they are
+    pending_messages: VecDeque<PipeMessage>,
     current_term_command: Option<String>,
-    command_queue: VecDeque<Command>,
+    current_pane_id: Option<u32>,
+    term_command_cache: Option<TermCommandCache>,
+    command_queue: VecDeque<QueuedCommand>,
+    edge_queries: VecDeque<QueuedCommand>,
+    next_command_id: u64,
+    // Set while `list-clients` fails, commands then run as plain Zellij actions
+    degraded: bool,
+    probe_id: Option<u64>,
+    // Ids of queued commands in the order their `set_timeout` fires
+    pending_timeouts: VecDeque<u64>,
+    pane_tracker: PaneTracker,
 
     // Configuration
-    move_mod: Mod,
-    resize_mod: Mod,
+    config: Config,
+    config_issues: Vec<ConfigIssue>,
+}
+
+// Last `list-clients` answer, valid while the pane keeps focus and the TTL has not passed
+struct TermCommandCache {
+    pane_id: u32,
+    term_command: Option<String>,
+    updated: DateTime<Utc>,
 }
 
+#[derive(Clone, Copy, PartialEq)]
 enum Command {
     MoveFocus(Direction),
     MoveFocusOrTab(Direction),
-    Resize(Direction),
+    MoveFocusOrSplit(Direction),
+    Resize(Resize, Direction),
+    Swap(Direction),
+    Previous,
 }
 
-#[derive(Debug)]
-enum Mod {
-    Ctrl,
-    Alt,
+struct QueuedCommand {
+    id: u64,
+    command: Command,
+    target: ClientTarget,
+    config: Option<Config>,
+    // Identical commands arriving while this one waits, e.g. from key repeat
+    repeat: usize,
 }
 
+// Keys and actions always reach the pane focused by the client that loaded the plugin
+#[derive(PartialEq)]
+enum ClientTarget {
+    Pane(u32),
+    // Several panes are focused by different clients
+    Any,
+    // The message names a pane the actions would not reach
+    NotFocused,
+}
+
+// Zellij grows or shrinks a pane by this percentage of the tab per resize
+const ZELLIJ_RESIZE_PERCENT: usize = 5;
+
+const TARGET_ARGS: &[&str] = &["pane_id"];
+
+// Sent back by editor plugins when their window cannot move further
+const EDITOR_AT_EDGE: &str = "editor_at_edge";
+
+// Identifies which `run_command` a `RunCommandResult` belongs to
+const CONTEXT_KIND: &str = "kind";
+const CONTEXT_ID: &str = "id";
+const LIST_CLIENTS: &str = "list_clients";
+const NEW_PANE: &str = "new_pane";
+const NVIM_EDGE: &str = "nvim_edge";
+
 register_plugin!(State);
 
 impl ZellijPlugin for State {
     fn load(&mut self, configuration: BTreeMap<String, String>) {
-        self.parse_configuration(configuration);
+        let (config, config_issues) = Config::parse(&configuration);
+        self.config = config;
+        self.config_issues = config_issues;
+        for issue in &self.config_issues {
+            eprintln!("vim-zellij-navigator: {}", issue);
+        }
 
         request_permission(&[
             PermissionType::RunCommands,
             PermissionType::WriteToStdin,
             PermissionType::ChangeApplicationState,
+            PermissionType::ReadApplicationState,
         ]);
         subscribe(&[
             EventType::PermissionRequestResult,
             EventType::RunCommandResult,
+            EventType::PaneUpdate,
+            EventType::TabUpdate,
+            EventType::SessionUpdate,
+            EventType::Timer,
         ]);
-        if self.permissions_granted {
+        if self.permissions_granted && !self.has_config_errors() {
             hide_self();
         }
     }
 
     fn update(&mut self, event: Event) -> bool {
         match event {
-            Event::RunCommandResult(_, stdout, _, _) => {
-                let stdout = String::from_utf8(stdout).unwrap();
+            Event::RunCommandResult(exit_code, stdout, stderr, context) => {
+                self.handle_command_result(exit_code, stdout, stderr, context)
+            }
 
-                self.current_term_command = term_command_from_client_list(stdout);
+            Event::Timer(_) => self.handle_timeout(),
 
-                if !self.command_queue.is_empty() {
-                    let command = self.command_queue.pop_front().unwrap();
-                    self.execute_command(command);
-                }
+            Event::TabUpdate(tabs) => {
+                self.pane_tracker.update_tabs(tabs);
+                self.invalidate_term_command_cache();
             }
-
-            Event::PermissionRequestResult(permission) => {
-                self.permissions_granted = match permission {
-                    PermissionStatus::Granted => true,
-                    PermissionStatus::Denied => false,
-                };
-                if self.permissions_granted {
-                    hide_self();
-                }
+            Event::PaneUpdate(manifest) => {
+                self.pane_tracker.update_panes(manifest);
+                self.invalidate_term_command_cache();
             }
+            Event::SessionUpdate(sessions, _) => self.pane_tracker.update_sessions(sessions),
+
+            Event::PermissionRequestResult(permission) => self.handle_permission_result(permission),
             _ => {}
         }
         true
     }
 
-    fn render(&mut self, _rows: usize, _cols: usize) {}
+    fn render(&mut self, _rows: usize, _cols: usize) {
+        if self.permissions_denied {
+            println!(
+                "{} vim-zellij-navigator cannot run commands, read or change the session or write to panes without its permissions.",
+                Red.bold().paint("error")
+            );
+            println!("Navigation stays disabled until the plugin is reloaded and the permissions are granted.");
+            println!();
+        }
+        if self.config_issues.is_empty() {
+            return;
+        }
 
-    fn pipe(&mut self, pipe_message: PipeMessage) -> bool {
-        if let Some(command) = parse_command(pipe_message) {
-            self.handle_command(command);
+        println!("vim-zellij-navigator configuration problems:");
+        for issue in &self.config_issues {
+            let label = match issue.severity {
+                Severity::Error => Red.bold().paint("error"),
+                Severity::Warning => Yellow.bold().paint("warning"),
+            };
+            println!("{} {}", label, issue);
         }
-        true
+        println!();
+        println!("Invalid options fall back to their defaults.");
     }
-}
 
-impl Default for State {
-    fn default() -> Self {
-        Self {
-            permissions_granted: false,
-            current_term_command: None,
-            command_queue: VecDeque::new(),
+    fn pipe(&mut self, pipe_message: PipeMessage) -> bool {
+        // Actions without permissions fail silently, so messages wait for the answer of the user
+        if !self.permissions_granted {
+            if !self.permissions_denied {
+                if self.pending_messages.len() >= self.config.max_queued_commands {
+                    self.pending_messages.pop_front();
+                }
+                self.pending_messages.push_back(pipe_message);
+            }
+            return false;
+        }
 
-            move_mod: Mod::Ctrl,
-            resize_mod: Mod::Alt,
+        let target = self.client_target(&pipe_message);
+        let config = self.message_config(&pipe_message);
+        if pipe_message.name == EDITOR_AT_EDGE {
+            if let Some(direction) = parse_editor_at_edge(&pipe_message) {
+                self.editor_at_edge(direction, target, config);
+            }
+        } else if let Some(command) = parse_command(pipe_message) {
+            self.handle_command(command, target, config);
         }
+        true
     }
 }
 
 impl State {
-    fn handle_command(&mut self, command: Command) {
-        self.command_queue.push_back(command);
-        run_command(&["zellij", "action", "list-clients"], BTreeMap::new());
+    // Warnings are only logged, they do not keep the pane on screen
+    fn has_config_errors(&self) -> bool {
+        self.config_issues
+            .iter()
+            .any(|issue| issue.severity == Severity::Error)
     }
 
-    fn execute_command(&mut self, command: Command) {
-        if self.current_pane_is_vim() {
-            write_chars(&self.command_to_keybind(&command));
-            return;
+    fn handle_permission_result(&mut self, permission: PermissionStatus) {
+        match permission {
+            PermissionStatus::Granted => {
+                self.permissions_granted = true;
+                // Configuration errors stay visible until the user closes the pane
+                if !self.has_config_errors() {
+                    hide_self();
+                }
+                while let Some(pipe_message) = self.pending_messages.pop_front() {
+                    self.pipe(pipe_message);
+                }
+            }
+            PermissionStatus::Denied => {
+                self.permissions_granted = false;
+                self.pending_messages.clear();
+                // Explain once why navigation does nothing, the user can close the pane after
+                if !self.permissions_denied {
+                    self.permissions_denied = true;
+                    show_self(true);
+                }
+            }
         }
+    }
 
-        match command {
-            Command::MoveFocus(direction) => move_focus(direction),
-            Command::MoveFocusOrTab(direction) => move_focus_or_tab(direction),
-            Command::Resize(direction) => {
-                resize_focused_pane_with_direction(Resize::Increase, direction)
+    fn handle_command_result(
+        &mut self,
+        exit_code: Option<i32>,
+        stdout: Vec<u8>,
+        stderr: Vec<u8>,
+        context: BTreeMap<String, String>,
+    ) {
+        let id = context.get(CONTEXT_ID).and_then(|id| id.parse().ok());
+        match context.get(CONTEXT_KIND).map(String::as_str) {
+            Some(LIST_CLIENTS) => {}
+            Some(NVIM_EDGE) => return self.handle_edge_result(id, exit_code, stdout, stderr),
+            _ => {
+                if exit_code != Some(0) {
+                    eprintln!(
+                        "vim-zellij-navigator: command failed: {}",
+                        String::from_utf8_lossy(&stderr)
+                    );
+                }
+                return;
             }
         }
+
+        let clients = decode_client_list(exit_code, &stdout, &stderr);
+        if let Err(err) = &clients {
+            if !self.degraded {
+                eprintln!(
+                    "vim-zellij-navigator: {}, falling back to plain Zellij actions",
+                    err
+                );
+            }
+        }
+        self.degraded = clients.is_err();
+        if id.is_some() && id == self.probe_id {
+            self.probe_id = None;
+        }
+
+        let queued = match id.and_then(|id| take_queued(&mut self.command_queue, id)) {
+            Some(queued) => queued,
+            None => return,
+        };
+        let clients = match clients {
+            Ok(clients) => clients,
+            Err(_) => {
+                self.execute_without_term_command(queued.command, queued.config, queued.repeat);
+                return;
+            }
+        };
+
+        let wrappers = &queued
+            .config
+            .as_ref()
+            .unwrap_or(&self.config)
+            .wrapper_programs;
+        let client = term_command_of_target(clients, &queued.target, wrappers);
+        let pane_id = match (&client, &queued.target) {
+            (Some((pane_id, _)), _) | (None, ClientTarget::Pane(pane_id)) => Some(*pane_id),
+            _ => None,
+        };
+        self.current_pane_id = pane_id;
+        self.current_term_command = client.map(|(_, command)| command);
+        // Answers for several clients are not tied to the pane the keys reach
+        self.term_command_cache = match queued.target {
+            ClientTarget::Pane(pane_id) => Some(TermCommandCache {
+                pane_id,
+                term_command: self.current_term_command.clone(),
+                updated: Utc::now(),
+            }),
+            ClientTarget::Any | ClientTarget::NotFocused => None,
+        };
+        self.dispatch_command(queued.command, queued.config, queued.repeat);
     }
 
-    fn current_pane_is_vim(&self) -> bool {
-        if let Some(current_command) = &self.current_term_command {
-            if current_command == "nvim" || current_command == "vim" {
-                return true;
+    fn cached_term_command(
+        &self,
+        target: &ClientTarget,
+        config: &Config,
+    ) -> Option<&TermCommandCache> {
+        let cache = self.term_command_cache.as_ref()?;
+        let ttl = chrono::Duration::milliseconds((config.cache_ttl * 1000.0) as i64);
+        if Utc::now().signed_duration_since(cache.updated) >= ttl {
+            return None;
+        }
+        match target {
+            ClientTarget::Pane(pane_id) if *pane_id == cache.pane_id => Some(cache),
+            _ => None,
+        }
+    }
+
+    // An unknown focus, e.g. with several clients, may have moved away from the cached pane
+    fn invalidate_term_command_cache(&mut self) {
+        let still_focused = match (self.pane_tracker.focused_pane(), &self.term_command_cache) {
+            (Some(focused), Some(cache)) => !focused.is_plugin && focused.id == cache.pane_id,
+            _ => false,
+        };
+        if !still_focused {
+            self.term_command_cache = None;
+        }
+    }
+
+    // Without an answer from Neovim the key is forwarded as if no socket was configured
+    fn handle_edge_result(
+        &mut self,
+        id: Option<u64>,
+        exit_code: Option<i32>,
+        stdout: Vec<u8>,
+        stderr: Vec<u8>,
+    ) {
+        let queued = match id.and_then(|id| take_queued(&mut self.edge_queries, id)) {
+            Some(queued) => queued,
+            None => return,
+        };
+        let config = queued.config.as_ref().unwrap_or(&self.config);
+
+        if exit_code != Some(0) {
+            eprintln!(
+                "vim-zellij-navigator: Neovim edge query failed: {}",
+                String::from_utf8_lossy(&stderr)
+            );
+        }
+        let at_edge = exit_code == Some(0) && String::from_utf8_lossy(&stdout).trim() == "1";
+        for _ in 0..queued.repeat {
+            if at_edge {
+                self.execute_zellij_command(queued.command, config);
+            } else {
+                self.forward_keys(&queued.command, config);
             }
         }
-        false
     }
 
-    fn parse_configuration(&mut self, configuration: BTreeMap<String, String>) {
-        self.move_mod = configuration.get("move_mod").map_or(Mod::Ctrl, |f| {
-            string_to_mod(f).expect("Illegal modifier for move_mod")
-        });
-        self.resize_mod = configuration.get("resize_mod").map_or(Mod::Alt, |f| {
-            string_to_mod(f).expect("Illegal modifier for resize_mod")
+    fn handle_command(&mut self, command: Command, target: ClientTarget, config: Option<Config>) {
+        // Keys written now would land in another pane, Zellij actions are still safe
+        if target == ClientTarget::NotFocused {
+            self.execute_without_term_command(command, config, 1);
+            return;
+        }
+
+        let current_config = config.as_ref().unwrap_or(&self.config);
+
+        // Pane events only carry the launch command of command panes, a shell launched there can
+        // run anything later, so only a passthrough program skips `list-clients`
+        let term_command = match target {
+            ClientTarget::Pane(pane_id) => self
+                .pane_tracker
+                .terminal_command(pane_id)
+                .and_then(|command| {
+                    let command = command.split_whitespace().collect::<Vec<&str>>();
+                    unwrap_program(&command, &current_config.wrapper_programs)
+                })
+                .filter(|program| {
+                    current_config
+                        .passthrough_programs
+                        .iter()
+                        .any(|pattern| pattern.matches(program))
+                }),
+            ClientTarget::Any | ClientTarget::NotFocused => None,
+        };
+        if let Some(term_command) = term_command {
+            self.current_term_command = Some(term_command);
+            if let ClientTarget::Pane(pane_id) = target {
+                self.current_pane_id = Some(pane_id);
+            }
+            self.dispatch_command(command, config, 1);
+            return;
+        }
+
+        let cached = self
+            .cached_term_command(&target, config.as_ref().unwrap_or(&self.config))
+            .map(|cache| (cache.pane_id, cache.term_command.clone()));
+        if let Some((pane_id, term_command)) = cached {
+            self.current_pane_id = Some(pane_id);
+            self.current_term_command = term_command;
+            self.dispatch_command(command, config, 1);
+            return;
+        }
+
+        if self.degraded {
+            self.probe_list_clients();
+            self.execute_without_term_command(command, config, 1);
+            return;
+        }
+
+        // A burst of the same command shares the pending `list-clients` and is replayed at once
+        if let Some(last) = self.command_queue.back_mut() {
+            if last.command == command && last.target == target && last.config == config {
+                last.repeat += 1;
+                return;
+            }
+        }
+
+        // A queue this long means `list-clients` stopped answering, the oldest command is stale
+        if self.command_queue.len() >= self.config.max_queued_commands {
+            eprintln!("vim-zellij-navigator: too many commands waiting, dropping the oldest");
+            self.command_queue.pop_front();
+        }
+
+        let id = self.next_command_id();
+        self.command_queue.push_back(QueuedCommand {
+            id,
+            command,
+            target,
+            config,
+            repeat: 1,
         });
+        run_command(
+            &["zellij", "action", "list-clients"],
+            command_context(LIST_CLIENTS, id),
+        );
+        self.start_timeout(id);
     }
 
-    fn command_to_keybind(&mut self, command: &Command) -> String {
-        let mod_key = match command {
-            Command::MoveFocus(_) | Command::MoveFocusOrTab(_) => &self.move_mod,
-            Command::Resize(_) => &self.resize_mod,
+    // Every command waits equally long, so the timers fire in the order they were started
+    fn start_timeout(&mut self, id: u64) {
+        set_timeout(self.config.command_timeout);
+        self.pending_timeouts.push_back(id);
+    }
+
+    fn handle_timeout(&mut self) {
+        let id = match self.pending_timeouts.pop_front() {
+            Some(id) => id,
+            None => return,
+        };
+        if self.probe_id == Some(id) {
+            self.probe_id = None;
+            return;
+        }
+        if let Some(queued) = take_queued(&mut self.edge_queries, id) {
+            eprintln!("vim-zellij-navigator: Neovim did not answer in time");
+            let config = queued.config.as_ref().unwrap_or(&self.config);
+            for _ in 0..queued.repeat {
+                self.forward_keys(&queued.command, config);
+            }
+            return;
+        }
+
+        let queued = match take_queued(&mut self.command_queue, id) {
+            Some(queued) => queued,
+            None => return,
+        };
+        eprintln!(
+            "vim-zellij-navigator: list-clients did not answer in time, falling back to plain Zellij actions"
+        );
+        // Later commands do not wait for `list-clients` until a probe gets an answer
+        self.degraded = true;
+
+        let config = queued.config.as_ref().unwrap_or(&self.config);
+        // The running command of the last answer only applies when it was for the same pane
+        let same_pane = match queued.target {
+            ClientTarget::Pane(pane_id) => self.current_pane_id == Some(pane_id),
+            ClientTarget::Any | ClientTarget::NotFocused => false,
         };
+        match config.timeout_action {
+            TimeoutAction::Execute => {
+                for _ in 0..queued.repeat {
+                    if same_pane {
+                        self.execute_command(queued.command, config);
+                    } else {
+                        self.execute_zellij_command(queued.command, config);
+                    }
+                }
+            }
+            TimeoutAction::Drop => {}
+        }
+    }
+
+    // Without the running command the plain Zellij action is the best guess
+    fn execute_without_term_command(
+        &mut self,
+        command: Command,
+        config: Option<Config>,
+        repeat: usize,
+    ) {
+        self.current_term_command = None;
+        self.current_pane_id = None;
+        for _ in 0..repeat {
+            self.execute_zellij_command(command, config.as_ref().unwrap_or(&self.config));
+        }
+    }
+
+    // Probes are not queued, they only check whether `list-clients` works again
+    fn probe_list_clients(&mut self) {
+        if self.probe_id.is_none() {
+            let id = self.next_command_id();
+            self.probe_id = Some(id);
+            run_command(
+                &["zellij", "action", "list-clients"],
+                command_context(LIST_CLIENTS, id),
+            );
+            self.start_timeout(id);
+        }
+    }
+
+    fn next_command_id(&mut self) -> u64 {
+        self.next_command_id += 1;
+        self.next_command_id
+    }
+
+    // The editor already handled the key, so Zellij moves without asking the editor again
+    fn editor_at_edge(&self, direction: Direction, target: ClientTarget, config: Option<Config>) {
+        // Reports from a pane that lost focus in the meantime are outdated
+        if target == ClientTarget::NotFocused {
+            return;
+        }
+        let config = config.as_ref().unwrap_or(&self.config);
+        self.execute_zellij_command(Command::MoveFocus(direction), config);
+    }
+
+    // Messages carry no client, the focused pane is only known while a single pane is focused
+    fn client_target(&self, pipe_message: &PipeMessage) -> ClientTarget {
+        let requested = pipe_message
+            .args
+            .get("pane_id")
+            .and_then(|id| id.parse::<u32>().ok());
+        match (self.pane_tracker.focused_pane(), requested) {
+            (Some(pane), _) if pane.is_plugin => ClientTarget::NotFocused,
+            (Some(pane), Some(pane_id)) if pane.id != pane_id => ClientTarget::NotFocused,
+            (Some(pane), _) => ClientTarget::Pane(pane.id),
+            (None, _) => ClientTarget::Any,
+        }
+    }
+
+    // Options given with the message only apply to that message
+    fn message_config(&self, pipe_message: &PipeMessage) -> Option<Config> {
+        let payload_options = pipe_message
+            .payload
+            .iter()
+            .flat_map(|payload| payload.split_whitespace())
+            .filter_map(|option| option.split_once('='));
+        let overrides = pipe_message
+            .args
+            .iter()
+            .filter(|(key, _)| !TARGET_ARGS.contains(&key.as_str()))
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .chain(payload_options)
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<BTreeMap<String, String>>();
+        if overrides.is_empty() {
+            return None;
+        }
 
+        let (config, issues) = self.config.with_overrides(&overrides);
+        for issue in issues {
+            eprintln!("vim-zellij-navigator: {}", issue);
+        }
+        Some(config)
+    }
+
+    // With `nvim_socket` Neovim is asked whether its window is at the edge before anything is sent
+    fn dispatch_command(&mut self, command: Command, config: Option<Config>, repeat: usize) {
+        let query = self.nvim_edge_query(&command, config.as_ref().unwrap_or(&self.config));
+        match (query, self.current_pane_id) {
+            (Some((socket, expr)), Some(pane_id)) => {
+                let id = self.next_command_id();
+                run_command(
+                    &["nvim", "--server", &socket, "--remote-expr", &expr],
+                    command_context(NVIM_EDGE, id),
+                );
+                self.edge_queries.push_back(QueuedCommand {
+                    id,
+                    command,
+                    target: ClientTarget::Pane(pane_id),
+                    config,
+                    repeat,
+                });
+                self.start_timeout(id);
+            }
+            _ => {
+                for _ in 0..repeat {
+                    self.execute_command(command, config.as_ref().unwrap_or(&self.config));
+                }
+            }
+        }
+    }
+
+    fn nvim_edge_query(&self, command: &Command, config: &Config) -> Option<(String, String)> {
+        let template = config.nvim_socket.as_ref()?;
         let direction = match command {
             Command::MoveFocus(direction)
             | Command::MoveFocusOrTab(direction)
-            | Command::Resize(direction) => direction,
+            | Command::MoveFocusOrSplit(direction) => direction,
+            _ => return None,
         };
+        // Only programs that receive the keys when they are not at the edge are asked
+        let program = self.current_term_command.as_ref()?;
+        if !self.current_pane_is_passthrough(config)
+            || !config
+                .nvim_programs
+                .iter()
+                .any(|pattern| pattern.matches(program))
+        {
+            return None;
+        }
+
+        let socket = template.replace("{pane_id}", &self.current_pane_id?.to_string());
+        let expr = expand_template("winnr('{dir}') == winnr()", Some(direction));
+        Some((socket, expr))
+    }
 
-        match mod_key {
-            Mod::Ctrl => ctrl_keybinding(direction),
-            Mod::Alt => alt_keybinding(direction),
+    fn execute_command(&self, command: Command, config: &Config) {
+        if self.current_pane_is_passthrough(config) {
+            self.forward_keys(&command, config);
+        } else {
+            self.execute_zellij_command(command, config);
         }
     }
-}
 
-fn term_command_from_client_list(cl: String) -> Option<String> {
-    let clients = cl.split('\n').skip(1).collect::<Vec<&str>>();
-    if clients.is_empty() {
-        return None;
+    fn forward_keys(&self, command: &Command, config: &Config) {
+        let count = match command {
+            Command::Resize(_, direction) => self.resize_count(direction, config),
+            _ => None,
+        };
+        let keybind = self.command_to_keybind(command, config);
+        match count {
+            Some(count) => write_chars(&format!("{}{}", count, keybind)),
+            None => write_chars(&keybind),
+        }
     }
 
-    let columns = clients[0].split_whitespace().collect::<Vec<&str>>();
-    if columns.len() < 3 {
-        return None;
+    fn execute_zellij_command(&self, command: Command, config: &Config) {
+        match command {
+            Command::MoveFocus(direction) => {
+                self.move_focus_or_edge(direction, config.on_edge, config)
+            }
+            Command::MoveFocusOrTab(direction) => {
+                self.move_focus_or_edge(direction, EdgeAction::Tab, config)
+            }
+            Command::MoveFocusOrSplit(direction) => {
+                self.move_focus_or_edge(direction, EdgeAction::NewPane, config)
+            }
+            Command::Resize(resize, direction) => {
+                for _ in 0..self.resize_repeats(&direction, config) {
+                    resize_focused_pane_with_direction(resize, direction);
+                }
+            }
+            Command::Swap(direction) => move_pane_with_direction(direction),
+            Command::Previous => {
+                if let Some(pane) = self.pane_tracker.previous_pane() {
+                    focus_pane(pane);
+                }
+            }
+        }
     }
 
-    let is_terminal = columns[1].starts_with("terminal");
-    let no_command = columns[2] == "N/A";
-    if !is_terminal || no_command {
-        return None;
+    fn move_focus_or_edge(&self, direction: Direction, on_edge: EdgeAction, config: &Config) {
+        // Without a known layout Zellij decides whether the focus is at the edge
+        if self.pane_tracker.has_neighbor(&direction) != Some(false) {
+            match on_edge {
+                EdgeAction::Tab => move_focus_or_tab(direction),
+                _ => move_focus(direction),
+            }
+            return;
+        }
+
+        match on_edge {
+            EdgeAction::Nothing => {}
+            EdgeAction::Wrap => {
+                if let Some(pane) = self.pane_tracker.wrap_target(&direction) {
+                    focus_pane(pane);
+                }
+            }
+            EdgeAction::Tab => move_focus_or_tab(direction),
+            EdgeAction::NewPane => open_pane_in_direction(&direction, config),
+            EdgeAction::NewTab => new_tab(),
+            EdgeAction::Session => {
+                if let Some(session) = self.pane_tracker.adjacent_session(&direction) {
+                    switch_session(Some(session));
+                }
+            }
+        }
+    }
+
+    fn resize_repeats(&self, direction: &Direction, config: &Config) -> usize {
+        let extent = self.pane_tracker.tab_extent(direction);
+        let repeats = match (config.resize_step, extent) {
+            (Some(ResizeStep::Percent(percent)), _) => percent.div_ceil(ZELLIJ_RESIZE_PERCENT),
+            (Some(ResizeStep::Cells(cells)), Some(extent)) if extent > 0 => {
+                (cells * 100).div_ceil(extent * ZELLIJ_RESIZE_PERCENT)
+            }
+            (Some(ResizeStep::Cells(_)), _) | (None, _) => 1,
+        };
+        repeats.max(1)
+    }
+
+    // Count prefix for the editor, e.g. `5<A-h>`, so it resizes by the same amount
+    fn resize_count(&self, direction: &Direction, config: &Config) -> Option<usize> {
+        match config.resize_step? {
+            ResizeStep::Cells(cells) => Some(cells),
+            ResizeStep::Percent(percent) => {
+                let extent = self.pane_tracker.tab_extent(direction);
+                Some(
+                    extent
+                        .map_or(percent, |extent| extent * percent / 100)
+                        .max(1),
+                )
+            }
+        }
+    }
+
+    fn current_pane_is_passthrough(&self, config: &Config) -> bool {
+        if let Some(current_command) = &self.current_term_command {
+            return config
+                .passthrough_programs
+                .iter()
+                .any(|pattern| pattern.matches(current_command));
+        }
+        false
+    }
+
+    fn command_to_keybind(&self, command: &Command, config: &Config) -> String {
+        let (kind, direction) = match command {
+            Command::MoveFocus(direction)
+            | Command::MoveFocusOrTab(direction)
+            | Command::MoveFocusOrSplit(direction) => (CommandKind::Move, Some(direction)),
+            Command::Resize(Resize::Increase, direction) => (CommandKind::Resize, Some(direction)),
+            Command::Resize(Resize::Decrease, direction) => (CommandKind::Shrink, Some(direction)),
+            Command::Swap(direction) => (CommandKind::Swap, Some(direction)),
+            Command::Previous => (CommandKind::Previous, None),
+        };
+
+        if let Some(keys) = self.profile_keybind(kind, direction, config) {
+            return keys;
+        }
+        if let Some(keys) = config.key_bindings.get(&(kind, direction.copied())) {
+            return encode_keys(keys, config.key_encoding);
+        }
+
+        let mod_key = match kind {
+            CommandKind::Move | CommandKind::Swap | CommandKind::Previous => &config.move_mod,
+            CommandKind::Resize | CommandKind::Shrink => &config.resize_mod,
+        };
+        let mut modifiers = match mod_key {
+            Mod::Ctrl => Modifiers {
+                ctrl: true,
+                ..Modifiers::default()
+            },
+            Mod::Alt => Modifiers {
+                alt: true,
+                ..Modifiers::default()
+            },
+        };
+        // Swapping uses the window commands of Vim, e.g. <C-w>H
+        if let (CommandKind::Swap, Some(direction)) = (kind, direction) {
+            let key = match direction_key(direction) {
+                Key::Char(c) => Key::Char(c.to_ascii_uppercase()),
+                key => key,
+            };
+            let chords = [
+                KeyChord::with_modifiers(Key::Char('w'), modifiers),
+                KeyChord::new(key),
+            ];
+            return encode_keys(&chords, config.key_encoding);
+        }
+        // Shrinking adds Shift, e.g. Alt+Shift+h
+        modifiers.shift = kind == CommandKind::Shrink;
+        // Previous pane is <C-\> like in vim-tmux-navigator
+        let key = direction.map_or(Key::Char('\\'), direction_key);
+        let chord = KeyChord::with_modifiers(key, modifiers);
+        encode_keys(&[chord], config.key_encoding)
     }
 
-    let command = columns[2].split('/').last()?;
-    Some(command.to_string())
+    fn profile_keybind(
+        &self,
+        kind: CommandKind,
+        direction: Option<&Direction>,
+        config: &Config,
+    ) -> Option<String> {
+        let profile = config.profiles.get(self.current_term_command.as_ref()?)?;
+        let template = profile.get(&kind)?;
+
+        let keys = parse_vim_keys(&expand_template(template, direction)).ok()?;
+        Some(encode_keys(&keys, config.key_encoding))
+    }
 }
 
-fn ctrl_keybinding(direction: &Direction) -> String {
+// Without `--cwd` and a command Zellij opens the pane in the cwd of the focused pane
+fn open_pane_in_direction(direction: &Direction, config: &Config) {
     let direction = match direction {
-        Direction::Left => "\u{0008}",
-        Direction::Right => "\u{000C}",
-        Direction::Up => "\u{000B}",
-        Direction::Down => "\u{000A}",
+        Direction::Left => "left",
+        Direction::Right => "right",
+        Direction::Up => "up",
+        Direction::Down => "down",
     };
-    direction.to_string()
+    let mut command = vec!["zellij", "action", "new-pane", "--direction", direction];
+    if let Some(cwd) = &config.split_cwd {
+        command.extend(&["--cwd", cwd.as_str()]);
+    }
+    if let Some(split_command) = &config.split_command {
+        command.push("--");
+        command.extend(split_command.split_whitespace());
+    }
+    run_command(&command, context(NEW_PANE));
 }
 
-fn alt_keybinding(direction: &Direction) -> String {
-    let direction = match direction {
-        Direction::Left => "\u{1b}!",
-        Direction::Up => "\u{1b}@",
-        Direction::Right => "\u{1b}#",
-        Direction::Down => "\u{1b}$",
+fn context(kind: &str) -> BTreeMap<String, String> {
+    let mut context = BTreeMap::new();
+    context.insert(CONTEXT_KIND.to_string(), kind.to_string());
+    context
+}
+
+// Results are matched to their command by id, other commands may finish in between
+fn command_context(kind: &str, id: u64) -> BTreeMap<String, String> {
+    let mut context = context(kind);
+    context.insert(CONTEXT_ID.to_string(), id.to_string());
+    context
+}
+
+fn take_queued(queue: &mut VecDeque<QueuedCommand>, id: u64) -> Option<QueuedCommand> {
+    let position = queue.iter().position(|queued| queued.id == id)?;
+    queue.remove(position)
+}
+
+fn focus_pane(pane: PaneId) {
+    if pane.is_plugin {
+        focus_plugin_pane(pane.id, false);
+    } else {
+        focus_terminal_pane(pane.id, false);
+    }
+}
+
+// Command lines are not guaranteed to be UTF-8, e.g. Latin-1 file names
+fn decode_client_list(
+    exit_code: Option<i32>,
+    stdout: &[u8],
+    stderr: &[u8],
+) -> Result<Vec<ClientInfo>, String> {
+    match exit_code {
+        Some(0) => {
+            parse_client_list(&String::from_utf8_lossy(stdout)).map_err(|err| err.to_string())
+        }
+        _ => Err(format!(
+            "list-clients failed: {}",
+            String::from_utf8_lossy(stderr).trim()
+        )),
+    }
+}
+
+fn term_command_of_target(
+    clients: Vec<ClientInfo>,
+    target: &ClientTarget,
+    wrappers: &[ProgramPattern],
+) -> Option<(u32, String)> {
+    let term_command = |client: &ClientInfo| {
+        if client.pane_kind != PaneKind::Terminal {
+            return None;
+        }
+        let mut command = vec![client.running_command.as_deref()?];
+        command.extend(client.args.iter().map(String::as_str));
+        Some((client.pane_id, unwrap_program(&command, wrappers)?))
     };
-    direction.to_string()
-    // String::from_utf8(vec![27, 91, 49, 59, 50, 49]).unwrap()
-    // &[0x1B, b'!']
-    // "\u{1b}!".to_string()
-    // let mut char_vec: Vec<char> = vec![0x1b as char];
-    // char_vec.push(match direction {
-    //     Direction::Left => 'h',
-    //     Direction::Right => 'l',
-    //     Direction::Up => 'k',
-    //     Direction::Down => 'j',
-    // });
-    // char_vec.iter().collect()
-}
-
-fn string_to_direction(s: &str) -> Option<Direction> {
-    match s {
-        "left" => Some(Direction::Left),
-        "right" => Some(Direction::Right),
-        "up" => Some(Direction::Up),
-        "down" => Some(Direction::Down),
-        _ => None,
+
+    match target {
+        ClientTarget::Pane(pane_id) => clients
+            .iter()
+            .find(|client| client.pane_kind == PaneKind::Terminal && client.pane_id == *pane_id)
+            .and_then(term_command),
+        // The client of the plugin is unknown, so keys are only sent when every client agrees
+        ClientTarget::Any => {
+            let mut commands = clients.iter().map(term_command);
+            let first = commands.next()??;
+            if commands.any(|command| command.as_ref() != Some(&first)) {
+                eprintln!(
+                    "vim-zellij-navigator: clients focus different panes, falling back to plain Zellij actions"
+                );
+                return None;
+            }
+            Some(first)
+        }
+        ClientTarget::NotFocused => None,
     }
 }
 
-fn string_to_mod(s: &str) -> Option<Mod> {
-    match s.to_lowercase().as_str() {
-        "ctrl" => Some(Mod::Ctrl),
-        "alt" => Some(Mod::Alt),
-        _ => None,
+fn parse_editor_at_edge(pipe_message: &PipeMessage) -> Option<Direction> {
+    // Every plugin instance receives a broadcast and each of them would move the focus
+    if !pipe_message.is_private {
+        eprintln!(
+            "{} is only handled when sent to one plugin with --plugin",
+            EDITOR_AT_EDGE
+        );
+        return None;
     }
+    let mut words = pipe_message
+        .payload
+        .iter()
+        .flat_map(|payload| payload.split_whitespace())
+        .filter(|word| !word.contains('='));
+    string_to_direction(words.next()?)
 }
 
 fn parse_command(pipe_message: PipeMessage) -> Option<Command> {
-    let payload = pipe_message.payload?;
+    let payload = pipe_message.payload.unwrap_or_default();
     let command = pipe_message.name;
+    if command == "previous" {
+        return Some(Command::Previous);
+    }
 
-    let direction = string_to_direction(payload.as_str())?;
+    // Options (`key=value`) are not part of the command itself
+    let mut words = payload
+        .split_whitespace()
+        .filter(|word| !word.contains('='));
+    let direction = string_to_direction(words.next()?)?;
 
     match command.as_str() {
         "move_focus" => Some(Command::MoveFocus(direction)),
         "move_focus_or_tab" => Some(Command::MoveFocusOrTab(direction)),
-        "resize" => Some(Command::Resize(direction)),
+        "move_focus_or_split" => Some(Command::MoveFocusOrSplit(direction)),
+        "swap" => Some(Command::Swap(direction)),
+        "resize" => match words.next() {
+            None | Some("increase") => Some(Command::Resize(Resize::Increase, direction)),
+            Some("decrease") => Some(Command::Resize(Resize::Decrease, direction)),
+            Some(_) => None,
+        },
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use programs::{parse_program_patterns, DEFAULT_WRAPPER_PROGRAMS};
+
+    const TWO_CLIENTS: &str = "\
+CLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND
+1 terminal_3 nvim src/main.rs
+2 terminal_5 bash
+";
+
+    fn term_command(output: &str, target: ClientTarget) -> Option<(u32, String)> {
+        let clients = parse_client_list(output).unwrap();
+        let wrappers = parse_program_patterns(DEFAULT_WRAPPER_PROGRAMS).unwrap();
+        term_command_of_target(clients, &target, &wrappers)
+    }
+
+    fn editor_at_edge_message(is_private: bool) -> PipeMessage {
+        PipeMessage::new(
+            PipeSource::Cli("1".to_string()),
+            EDITOR_AT_EDGE,
+            &Some("left on_edge=tab".to_string()),
+            &None,
+            is_private,
+        )
+    }
+
+    #[test]
+    fn editor_at_edge_sent_to_one_plugin_moves_once() {
+        assert_eq!(
+            parse_editor_at_edge(&editor_at_edge_message(true)),
+            Some(Direction::Left)
+        );
+    }
+
+    #[test]
+    fn broadcast_editor_at_edge_is_ignored() {
+        assert_eq!(parse_editor_at_edge(&editor_at_edge_message(false)), None);
+    }
+
+    #[test]
+    fn latin1_command_line_is_decoded_lossily() {
+        let stdout = b"CLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND\n1 terminal_2 nvim caf\xe9.txt\n";
+        let clients = decode_client_list(Some(0), stdout, b"").unwrap();
+        assert_eq!(clients.len(), 1);
+        assert_eq!(clients[0].running_command.as_deref(), Some("nvim"));
+        assert_eq!(clients[0].args, vec!["caf\u{fffd}.txt".to_string()]);
+    }
+
+    #[test]
+    fn failed_list_clients_reports_stderr() {
+        let stderr = b"There is no active session!\n";
+        assert_eq!(
+            decode_client_list(Some(1), b"", stderr),
+            Err("list-clients failed: There is no active session!".to_string())
+        );
+        assert_eq!(
+            decode_client_list(None, b"CLIENT_ID", b"killed"),
+            Err("list-clients failed: killed".to_string())
+        );
+    }
+
+    #[test]
+    fn unparsable_list_clients_is_an_error() {
+        assert_eq!(
+            decode_client_list(Some(0), b"\xff\xfe\n", b""),
+            Err("list-clients header has no CLIENT_ID column".to_string())
+        );
+    }
+
+    #[test]
+    fn any_target_with_clients_on_different_panes_sends_no_keys() {
+        assert_eq!(term_command(TWO_CLIENTS, ClientTarget::Any), None);
+    }
+
+    #[test]
+    fn pane_target_selects_the_client_focused_on_that_pane() {
+        let output = "\
+CLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND
+1 terminal_3 bash
+2 terminal_5 sudo -u root nvim /etc/hosts
+";
+        assert_eq!(
+            term_command(output, ClientTarget::Pane(5)),
+            Some((5, "nvim".to_string()))
+        );
+        assert_eq!(
+            term_command(output, ClientTarget::Pane(3)),
+            Some((3, "bash".to_string()))
+        );
+        assert_eq!(term_command(output, ClientTarget::Pane(4)), None);
+    }
+
+    #[test]
+    fn pane_target_ignores_plugin_panes_with_the_same_id() {
+        let output = "\
+CLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND
+1 plugin_5 N/A
+2 terminal_5 nvim
+";
+        assert_eq!(
+            term_command(output, ClientTarget::Pane(5)),
+            Some((5, "nvim".to_string()))
+        );
+    }
+
+    #[test]
+    fn any_target_with_clients_on_the_same_pane() {
+        let output = "\
+CLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND
+1 terminal_3 nvim a.rs
+2 terminal_3 nvim a.rs
+";
+        assert_eq!(
+            term_command(output, ClientTarget::Any),
+            Some((3, "nvim".to_string()))
+        );
+    }
+
+    #[test]
+    fn any_target_with_the_same_program_in_different_panes_sends_no_keys() {
+        let output = "\
+CLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND
+1 terminal_3 nvim a.rs
+2 terminal_5 nvim b.rs
+";
+        assert_eq!(term_command(output, ClientTarget::Any), None);
+    }
+
+    #[test]
+    fn not_focused_target_sends_no_keys() {
+        assert_eq!(term_command(TWO_CLIENTS, ClientTarget::NotFocused), None);
+    }
+}
@@ -0,0 +1,403 @@
+use zellij_tile::prelude::*;
+
+use std::collections::HashMap;
+
+const FOCUS_HISTORY_LENGTH: usize = 32;
+
+#[derive(Default)]
+pub struct PaneTracker {
+    tabs: Vec<TabInfo>,
+    panes: HashMap<usize, Vec<PaneInfo>>,
+    focus_history: HashMap<usize, Vec<PaneId>>,
+    sessions: Vec<SessionInfo>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaneId {
+    pub id: u32,
+    pub is_plugin: bool,
+}
+
+impl PaneId {
+    fn of(pane: &PaneInfo) -> Self {
+        Self {
+            id: pane.id,
+            is_plugin: pane.is_plugin,
+        }
+    }
+}
+
+impl PaneTracker {
+    pub fn update_tabs(&mut self, tabs: Vec<TabInfo>) {
+        self.tabs = tabs;
+        self.update_focus_history();
+    }
+
+    pub fn update_panes(&mut self, manifest: PaneManifest) {
+        self.panes = manifest.panes;
+        self.update_focus_history();
+    }
+
+    // Closed panes are dropped from the history so it only holds panes that can be focused
+    fn update_focus_history(&mut self) {
+        for tab in &self.tabs {
+            let panes = match self.panes.get(&tab.position) {
+                Some(panes) => panes,
+                None => continue,
+            };
+
+            let history = self.focus_history.entry(tab.position).or_default();
+            history.retain(|pane_id| panes.iter().any(|pane| PaneId::of(pane) == *pane_id));
+            history.dedup();
+
+            if let Some(focused) = focused_in_tab(tab, panes).map(PaneId::of) {
+                if history.last() != Some(&focused) {
+                    history.push(focused);
+                }
+            }
+            if history.len() > FOCUS_HISTORY_LENGTH {
+                history.remove(0);
+            }
+        }
+
+        let panes = &self.panes;
+        self.focus_history
+            .retain(|position, _| panes.contains_key(position));
+    }
+
+    pub fn previous_pane(&self) -> Option<PaneId> {
+        let tab = self.active_tab()?;
+        let history = self.focus_history.get(&tab.position)?;
+        history.iter().rev().nth(1).copied()
+    }
+
+    pub fn update_sessions(&mut self, mut sessions: Vec<SessionInfo>) {
+        sessions.sort_by(|a, b| a.name.cmp(&b.name));
+        self.sessions = sessions;
+    }
+
+    // Sessions are ordered by name, left and up go to the previous one
+    pub fn adjacent_session(&self, direction: &Direction) -> Option<&str> {
+        let current = self.sessions.iter().position(|s| s.is_current_session)?;
+        let count = self.sessions.len();
+        if count < 2 {
+            return None;
+        }
+        let adjacent = match direction {
+            Direction::Left | Direction::Up => (current + count - 1) % count,
+            Direction::Right | Direction::Down => (current + 1) % count,
+        };
+        Some(&self.sessions[adjacent].name)
+    }
+
+    // Clients on different tabs make several tabs active, then it is unknown which one is meant
+    pub fn active_tab(&self) -> Option<&TabInfo> {
+        let mut active = self.tabs.iter().filter(|tab| tab.active);
+        let tab = active.next()?;
+        if active.next().is_some() {
+            return None;
+        }
+        Some(tab)
+    }
+
+    pub fn focused_pane(&self) -> Option<&PaneInfo> {
+        let tab = self.active_tab()?;
+        focused_in_tab(tab, self.panes.get(&tab.position)?)
+    }
+
+    // Size of the tiled area of the active tab along the axis of `direction`
+    pub fn tab_extent(&self, direction: &Direction) -> Option<usize> {
+        let tab = self.active_tab()?;
+        let panes = self
+            .panes
+            .get(&tab.position)?
+            .iter()
+            .filter(|pane| !pane.is_floating && !pane.is_suppressed && pane.is_selectable);
+        match direction {
+            Direction::Left | Direction::Right => {
+                panes.map(|pane| pane.pane_x + pane.pane_columns).max()
+            }
+            Direction::Up | Direction::Down => panes.map(|pane| pane.pane_y + pane.pane_rows).max(),
+        }
+    }
+
+    pub fn has_neighbor(&self, direction: &Direction) -> Option<bool> {
+        let (focused, others) = self.tiled_layout()?;
+        Some(
+            others.iter().any(|pane| {
+                overlaps(focused, pane, direction) && is_beyond(focused, pane, direction)
+            }),
+        )
+    }
+
+    // The pane on the opposite side of the row or column, e.g. the rightmost pane when moving left
+    pub fn wrap_target(&self, direction: &Direction) -> Option<PaneId> {
+        let (focused, others) = self.tiled_layout()?;
+        others
+            .into_iter()
+            .filter(|pane| overlaps(focused, pane, direction))
+            .max_by_key(|pane| {
+                let (start, end, _, _) = span(pane, direction);
+                let position = match direction {
+                    Direction::Left | Direction::Up => end,
+                    Direction::Right | Direction::Down => -start,
+                };
+                (position, overlap(focused, pane, direction))
+            })
+            .map(PaneId::of)
+    }
+
+    fn tiled_layout(&self) -> Option<(&PaneInfo, Vec<&PaneInfo>)> {
+        let focused = self.focused_pane()?;
+        if focused.is_floating {
+            return None;
+        }
+
+        let tab = self.active_tab()?;
+        let others = self
+            .panes
+            .get(&tab.position)?
+            .iter()
+            .filter(|pane| !pane.is_floating && !pane.is_suppressed && pane.is_selectable)
+            .filter(|pane| PaneId::of(pane) != PaneId::of(focused))
+            .collect();
+        Some((focused, others))
+    }
+
+    // A held command pane keeps its launch command after the program exited
+    pub fn terminal_command(&self, pane_id: u32) -> Option<&str> {
+        let pane = self
+            .panes
+            .values()
+            .flatten()
+            .find(|pane| !pane.is_plugin && pane.id == pane_id)?;
+        if pane.exited || pane.is_held {
+            return None;
+        }
+        pane.terminal_command.as_deref()
+    }
+}
+
+// Floating panes only hold focus while they are visible. Clients focused on different panes make
+// several panes focused, then it is unknown which one is meant
+fn focused_in_tab<'a>(tab: &TabInfo, panes: &'a [PaneInfo]) -> Option<&'a PaneInfo> {
+    let mut focused = panes
+        .iter()
+        .filter(|pane| pane.is_focused && !pane.is_suppressed)
+        .filter(|pane| pane.is_floating == tab.are_floating_panes_visible);
+    let pane = focused.next()?;
+    if focused.next().is_some() {
+        return None;
+    }
+    Some(pane)
+}
+
+// Start and end along the axis of `direction`, followed by start and end across it
+fn span(pane: &PaneInfo, direction: &Direction) -> (isize, isize, isize, isize) {
+    let (x, y) = (pane.pane_x as isize, pane.pane_y as isize);
+    let (columns, rows) = (pane.pane_columns as isize, pane.pane_rows as isize);
+    match direction {
+        Direction::Left | Direction::Right => (x, x + columns, y, y + rows),
+        Direction::Up | Direction::Down => (y, y + rows, x, x + columns),
+    }
+}
+
+fn overlap(a: &PaneInfo, b: &PaneInfo, direction: &Direction) -> isize {
+    let (_, _, a_start, a_end) = span(a, direction);
+    let (_, _, b_start, b_end) = span(b, direction);
+    a_end.min(b_end) - a_start.max(b_start)
+}
+
+fn overlaps(a: &PaneInfo, b: &PaneInfo, direction: &Direction) -> bool {
+    overlap(a, b, direction) > 0
+}
+
+fn is_beyond(focused: &PaneInfo, pane: &PaneInfo, direction: &Direction) -> bool {
+    let (focused_start, focused_end, _, _) = span(focused, direction);
+    let (start, end, _, _) = span(pane, direction);
+    match direction {
+        Direction::Left | Direction::Up => end <= focused_start,
+        Direction::Right | Direction::Down => start >= focused_end,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(panes: Vec<PaneInfo>) -> PaneTracker {
+        let mut tracker = PaneTracker::default();
+        tracker.update_tabs(vec![TabInfo {
+            position: 0,
+            active: true,
+            ..TabInfo::default()
+        }]);
+        let mut manifest = PaneManifest::default();
+        manifest.panes.insert(0, panes);
+        tracker.update_panes(manifest);
+        tracker
+    }
+
+    fn command_pane(id: u32, command: &str) -> PaneInfo {
+        PaneInfo {
+            id,
+            is_selectable: true,
+            terminal_command: Some(command.to_string()),
+            ..PaneInfo::default()
+        }
+    }
+
+    fn pane(id: u32, (x, y): (usize, usize), (columns, rows): (usize, usize)) -> PaneInfo {
+        PaneInfo {
+            id,
+            is_selectable: true,
+            pane_x: x,
+            pane_y: y,
+            pane_columns: columns,
+            pane_rows: rows,
+            ..PaneInfo::default()
+        }
+    }
+
+    fn focused(pane: PaneInfo) -> PaneInfo {
+        PaneInfo {
+            is_focused: true,
+            ..pane
+        }
+    }
+
+    fn terminal(id: u32) -> Option<PaneId> {
+        Some(PaneId {
+            id,
+            is_plugin: false,
+        })
+    }
+
+    #[test]
+    fn leftmost_pane_wraps_to_the_rightmost_pane_of_its_row() {
+        let tracker = tracker(vec![
+            focused(pane(1, (0, 0), (40, 20))),
+            pane(2, (40, 0), (40, 20)),
+            pane(3, (80, 0), (40, 20)),
+        ]);
+        assert_eq!(tracker.has_neighbor(&Direction::Left), Some(false));
+        assert_eq!(tracker.has_neighbor(&Direction::Right), Some(true));
+        assert_eq!(tracker.wrap_target(&Direction::Left), terminal(3));
+        assert_eq!(tracker.tab_extent(&Direction::Left), Some(120));
+        assert_eq!(tracker.tab_extent(&Direction::Up), Some(20));
+    }
+
+    #[test]
+    fn wrap_prefers_the_pane_with_the_largest_overlap() {
+        // The right column is split into a short and a tall row
+        let tracker = tracker(vec![
+            focused(pane(1, (0, 0), (60, 20))),
+            pane(2, (60, 0), (60, 8)),
+            pane(3, (60, 8), (60, 12)),
+        ]);
+        assert_eq!(tracker.has_neighbor(&Direction::Left), Some(false));
+        assert_eq!(tracker.wrap_target(&Direction::Left), terminal(3));
+    }
+
+    #[test]
+    fn top_and_bottom_panes_wrap_to_each_other() {
+        let top = pane(1, (0, 0), (80, 10));
+        let bottom = pane(2, (0, 10), (80, 10));
+
+        let tracker_at_top = tracker(vec![focused(top.clone()), bottom.clone()]);
+        assert_eq!(tracker_at_top.has_neighbor(&Direction::Up), Some(false));
+        assert_eq!(tracker_at_top.has_neighbor(&Direction::Down), Some(true));
+        assert_eq!(tracker_at_top.wrap_target(&Direction::Up), terminal(2));
+
+        let tracker_at_bottom = tracker(vec![top, focused(bottom)]);
+        assert_eq!(
+            tracker_at_bottom.has_neighbor(&Direction::Down),
+            Some(false)
+        );
+        assert_eq!(tracker_at_bottom.wrap_target(&Direction::Down), terminal(1));
+        assert_eq!(tracker_at_bottom.tab_extent(&Direction::Down), Some(20));
+    }
+
+    #[test]
+    fn focused_floating_pane_has_no_tiled_neighbors() {
+        let mut tracker = tracker(vec![
+            pane(1, (0, 0), (40, 20)),
+            pane(2, (40, 0), (40, 20)),
+            focused(PaneInfo {
+                is_floating: true,
+                ..pane(3, (10, 5), (20, 10))
+            }),
+        ]);
+        tracker.update_tabs(vec![TabInfo {
+            position: 0,
+            active: true,
+            are_floating_panes_visible: true,
+            ..TabInfo::default()
+        }]);
+        assert_eq!(tracker.focused_pane().map(|pane| pane.id), Some(3));
+        assert_eq!(tracker.has_neighbor(&Direction::Left), None);
+        assert_eq!(tracker.wrap_target(&Direction::Left), None);
+        assert_eq!(tracker.tab_extent(&Direction::Left), Some(80));
+    }
+
+    fn focus(tracker: &mut PaneTracker, ids: &[u32], focused_id: u32) {
+        let panes = ids
+            .iter()
+            .map(|&id| PaneInfo {
+                is_focused: id == focused_id,
+                ..pane(id, (id as usize * 40, 0), (40, 20))
+            })
+            .collect();
+        let mut manifest = PaneManifest::default();
+        manifest.panes.insert(0, panes);
+        tracker.update_panes(manifest);
+    }
+
+    #[test]
+    fn closing_the_previous_pane_falls_back_to_the_one_before() {
+        let mut tracker = tracker(vec![]);
+        focus(&mut tracker, &[1, 2, 3], 1);
+        focus(&mut tracker, &[1, 2, 3], 2);
+        focus(&mut tracker, &[1, 2, 3], 3);
+        assert_eq!(tracker.previous_pane(), terminal(2));
+
+        focus(&mut tracker, &[1, 3], 3);
+        assert_eq!(tracker.previous_pane(), terminal(1));
+    }
+
+    #[test]
+    fn previous_pane_toggles_between_two_panes() {
+        let mut tracker = tracker(vec![]);
+        focus(&mut tracker, &[1, 2], 1);
+        assert_eq!(tracker.previous_pane(), None);
+
+        focus(&mut tracker, &[1, 2], 2);
+        assert_eq!(tracker.previous_pane(), terminal(1));
+        focus(&mut tracker, &[1, 2], 1);
+        assert_eq!(tracker.previous_pane(), terminal(2));
+        focus(&mut tracker, &[1, 2], 2);
+        assert_eq!(tracker.previous_pane(), terminal(1));
+    }
+
+    #[test]
+    fn running_command_pane_reports_its_command() {
+        let tracker = tracker(vec![command_pane(1, "nvim")]);
+        assert_eq!(tracker.terminal_command(1), Some("nvim"));
+        assert_eq!(tracker.terminal_command(2), None);
+    }
+
+    #[test]
+    fn exited_or_held_command_pane_reports_no_command() {
+        let exited = PaneInfo {
+            exited: true,
+            ..command_pane(1, "nvim")
+        };
+        let held = PaneInfo {
+            is_held: true,
+            ..command_pane(2, "nvim")
+        };
+        let tracker = tracker(vec![exited, held]);
+        assert_eq!(tracker.terminal_command(1), None);
+        assert_eq!(tracker.terminal_command(2), None);
+    }
+}
@@ -0,0 +1,248 @@
+use regex::Regex;
+
+pub const DEFAULT_PASSTHROUGH_PROGRAMS: &str = "vim nvim sudoedit";
+pub const DEFAULT_NVIM_PROGRAMS: &str = "nvim";
+pub const DEFAULT_WRAPPER_PROGRAMS: &str =
+    "sudo doas env nice nohup exec command time direnv nix nix-shell sh bash zsh fish dash";
+
+#[derive(Debug, Clone)]
+pub enum ProgramPattern {
+    Name(String),
+    Glob(String),
+    Regex(Regex),
+}
+
+impl ProgramPattern {
+    pub fn parse(pattern: &str) -> Result<Self, regex::Error> {
+        if pattern.len() > 1 && pattern.starts_with('/') && pattern.ends_with('/') {
+            let regex = Regex::new(&pattern[1..pattern.len() - 1])?;
+            return Ok(ProgramPattern::Regex(regex));
+        }
+        if pattern.contains(['*', '?']) {
+            return Ok(ProgramPattern::Glob(pattern.to_string()));
+        }
+        Ok(ProgramPattern::Name(pattern.to_string()))
+    }
+
+    pub fn matches(&self, program: &str) -> bool {
+        match self {
+            ProgramPattern::Name(name) => name == program,
+            ProgramPattern::Glob(glob) => {
+                let glob = glob.chars().collect::<Vec<char>>();
+                let program = program.chars().collect::<Vec<char>>();
+                glob_matches(&glob, &program)
+            }
+            ProgramPattern::Regex(regex) => regex.is_match(program),
+        }
+    }
+}
+
+impl PartialEq for ProgramPattern {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ProgramPattern::Name(a), ProgramPattern::Name(b)) => a == b,
+            (ProgramPattern::Glob(a), ProgramPattern::Glob(b)) => a == b,
+            (ProgramPattern::Regex(a), ProgramPattern::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+pub fn parse_program_patterns(s: &str) -> Result<Vec<ProgramPattern>, regex::Error> {
+    s.split_whitespace().map(ProgramPattern::parse).collect()
+}
+
+fn glob_matches(glob: &[char], s: &[char]) -> bool {
+    match glob.split_first() {
+        None => s.is_empty(),
+        Some(('*', rest)) => (0..=s.len()).any(|i| glob_matches(rest, &s[i..])),
+        Some(('?', rest)) => !s.is_empty() && glob_matches(rest, &s[1..]),
+        Some((c, rest)) => s.first() == Some(c) && glob_matches(rest, &s[1..]),
+    }
+}
+
+struct WrapperSpec {
+    value_flags: &'static [&'static str],
+    command_flags: &'static [&'static str],
+    skip_positionals: usize,
+    positional_is_command: bool,
+}
+
+enum Wrapped<'a, 'b> {
+    Command(&'a [&'b str]),
+    Program(String),
+}
+
+pub fn program_name(command: &str) -> Option<String> {
+    let program = command.split_whitespace().next()?;
+    let name = program.split('/').next_back()?;
+    Some(name.to_string())
+}
+
+// Walks past wrappers such as `sudo -u root nvim` or `direnv exec . nvim` to the wrapped program
+pub fn unwrap_program(command: &[&str], wrappers: &[ProgramPattern]) -> Option<String> {
+    let mut command = command;
+    loop {
+        let program = program_name(command.first()?)?;
+        if !wrappers.iter().any(|wrapper| wrapper.matches(&program)) {
+            return Some(program);
+        }
+
+        match wrapped_command(&program, &command[1..]) {
+            Some(Wrapped::Command(wrapped)) => command = wrapped,
+            Some(Wrapped::Program(wrapped)) => return Some(wrapped),
+            None => return Some(program),
+        }
+    }
+}
+
+fn wrapped_command<'a, 'b>(wrapper: &str, args: &'a [&'b str]) -> Option<Wrapped<'a, 'b>> {
+    // `nix run nixpkgs#neovim` names the package rather than the binary
+    if wrapper == "nix" && args.first() == Some(&"run") {
+        let installable = args[1..].iter().find(|arg| !arg.starts_with('-'))?;
+        let package = installable.rsplit('#').next()?;
+        return Some(Wrapped::Program(package_binary(package).to_string()));
+    }
+
+    let spec = wrapper_spec(wrapper);
+    let mut skip_positionals = spec.skip_positionals;
+    let mut i = 0;
+    while i < args.len() {
+        let arg = args[i];
+        if arg == "--" || spec.command_flags.contains(&arg) {
+            return non_empty(&args[i + 1..]);
+        }
+
+        if arg.starts_with('-') {
+            if spec.value_flags.contains(&arg) {
+                i += 1;
+            }
+        } else if arg.contains('=') {
+            // Environment assignments, e.g. `env TERM=xterm nvim`
+        } else if skip_positionals > 0 {
+            skip_positionals -= 1;
+        } else if spec.positional_is_command {
+            return non_empty(&args[i..]);
+        }
+        i += 1;
+    }
+    None
+}
+
+// Packages whose main binary has a different name
+fn package_binary(package: &str) -> &str {
+    match package {
+        "neovim" | "neovim-unwrapped" => "nvim",
+        "helix" => "hx",
+        _ => package,
+    }
+}
+
+fn non_empty<'a, 'b>(command: &'a [&'b str]) -> Option<Wrapped<'a, 'b>> {
+    if command.is_empty() {
+        return None;
+    }
+    Some(Wrapped::Command(command))
+}
+
+fn wrapper_spec(wrapper: &str) -> WrapperSpec {
+    let (value_flags, command_flags, skip_positionals, positional_is_command): (
+        &'static [&'static str],
+        &'static [&'static str],
+        usize,
+        bool,
+    ) = match wrapper {
+        "sudo" => (
+            &[
+                "-u", "-g", "-C", "-D", "-h", "-p", "-r", "-t", "-T", "-U", "--user", "--group",
+                "--chdir", "--prompt", "--role", "--type", "--host",
+            ],
+            &[],
+            0,
+            true,
+        ),
+        "doas" => (&["-u", "-C"], &[], 0, true),
+        "env" => (
+            &["-u", "-C", "--unset", "--chdir"],
+            &["-S", "--split-string"],
+            0,
+            true,
+        ),
+        "nice" => (&["-n", "--adjustment"], &[], 0, true),
+        "exec" => (&["-a"], &[], 0, true),
+        "time" => (&["-f", "-o", "--format", "--output"], &[], 0, true),
+        "direnv" => (&[], &[], 2, true),
+        "nix" => (&[], &["-c", "--command"], 0, false),
+        "nix-shell" => (
+            &["-p", "-A", "-I", "--packages", "--attr"],
+            &["--run", "--command"],
+            0,
+            false,
+        ),
+        "sh" | "bash" | "zsh" | "fish" | "dash" => {
+            (&["-o", "-O", "--rcfile", "--init-file"], &["-c"], 0, true)
+        }
+        _ => (&[], &[], 0, true),
+    };
+
+    WrapperSpec {
+        value_flags,
+        command_flags,
+        skip_positionals,
+        positional_is_command,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unwrap(command: &str) -> Option<String> {
+        let wrappers = parse_program_patterns(DEFAULT_WRAPPER_PROGRAMS).unwrap();
+        let command = command.split_whitespace().collect::<Vec<&str>>();
+        unwrap_program(&command, &wrappers)
+    }
+
+    fn is_passthrough(program: &str) -> bool {
+        parse_program_patterns(DEFAULT_PASSTHROUGH_PROGRAMS)
+            .unwrap()
+            .iter()
+            .any(|pattern| pattern.matches(program))
+    }
+
+    #[test]
+    fn nix_run_neovim_is_passthrough() {
+        assert_eq!(unwrap("nix run nixpkgs#neovim"), Some("nvim".to_string()));
+        assert!(is_passthrough("nvim"));
+    }
+
+    #[test]
+    fn options_of_wrappers_are_skipped() {
+        assert_eq!(unwrap("sudo -u root nvim"), Some("nvim".to_string()));
+        assert_eq!(unwrap("env TERM=xterm nvim"), Some("nvim".to_string()));
+        assert_eq!(unwrap("direnv exec . nvim"), Some("nvim".to_string()));
+    }
+
+    #[test]
+    fn commands_run_by_shells_are_unwrapped() {
+        assert_eq!(unwrap("bash -c nvim"), Some("nvim".to_string()));
+        assert_eq!(
+            unwrap("nix-shell -p neovim --run nvim"),
+            Some("nvim".to_string())
+        );
+        assert_eq!(unwrap("nix develop -c nvim"), Some("nvim".to_string()));
+    }
+
+    #[test]
+    fn interactive_shell_is_the_program() {
+        assert_eq!(unwrap("bash"), Some("bash".to_string()));
+        assert_eq!(unwrap("zsh -l"), Some("zsh".to_string()));
+        assert!(!is_passthrough("zsh"));
+    }
+
+    #[test]
+    fn sudoedit_is_passthrough() {
+        assert_eq!(unwrap("sudoedit /etc/hosts"), Some("sudoedit".to_string()));
+        assert!(is_passthrough("sudoedit"));
+    }
+}